@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+use crate::{
+    config::{parse_fraction, parse_weights},
+    rand,
+};
+
+/// Settings for randomly capitalizing words.
+#[derive(Clone, Debug)]
+pub struct Caps {
+    /// Fraction of words given a capitalization style.
+    pub fraction: f64,
+    /// Styles and their relative weights.
+    pub styles: Vec<(Style, f64)>,
+}
+
+impl Default for Caps {
+    fn default() -> Self {
+        Self {
+            fraction: 0.25,
+            styles: vec![
+                (Style::Capitalized, 4.0),
+                (Style::Upper, 1.0),
+                (Style::Title, 1.0),
+                (Style::Camel, 1.0),
+                (Style::Snake, 1.0),
+                (Style::Screaming, 1.0),
+            ],
+        }
+    }
+}
+
+impl Caps {
+    /// Reads a `[caps]` table, with `fraction` and a `styles` table of weights. Styles missing
+    /// from `styles` are not generated.
+    pub fn from_value(value: &toml::Value) -> Result<Self, String> {
+        let mut caps = Self::default();
+
+        if let Some(fraction) = value.get("fraction") {
+            caps.fraction = parse_fraction(fraction, "fraction")?;
+        }
+
+        if let Some(styles) = value.get("styles") {
+            caps.styles = parse_weights(styles, "styles", "style")?
+                .into_iter()
+                .map(|(style, weight)| Ok((style.parse()?, weight)))
+                .collect::<Result<_, String>>()?;
+        }
+
+        Ok(caps)
+    }
+
+    /// Capitalizes about a `fraction` of the words taken from `words` until there are `count`,
+    /// joining several words into one for the compound styles. Fewer are returned only if
+    /// `words` runs out.
+    pub fn apply(&self, mut words: impl Iterator<Item = String>, count: usize) -> Vec<String> {
+        let mut out = Vec::with_capacity(count);
+        while out.len() < count {
+            let word = match words.next() {
+                Some(word) => word,
+                None => break,
+            };
+            let style = match rand::choose_weighted(&self.styles) {
+                Some(&style) if rand::f64() < self.fraction => style,
+                _ => {
+                    out.push(word);
+                    continue;
+                }
+            };
+
+            let mut parts = vec![word];
+            if style.is_compound() {
+                let extra = rand::usize(1..=2);
+                while parts.len() <= extra {
+                    match words.next() {
+                        Some(word) => parts.push(word),
+                        None => break,
+                    }
+                }
+            }
+            out.push(style.apply(&parts));
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Style {
+    /// `Word`
+    Capitalized,
+    /// `WORD`
+    Upper,
+    /// `TitleCase`
+    Title,
+    /// `camelCase`
+    Camel,
+    /// `snake_case`
+    Snake,
+    /// `SCREAMING_CASE`
+    Screaming,
+}
+
+impl Style {
+    /// Whether the style joins several words.
+    fn is_compound(self) -> bool {
+        matches!(
+            self,
+            Self::Title | Self::Camel | Self::Snake | Self::Screaming
+        )
+    }
+
+    fn apply(self, parts: &[String]) -> String {
+        match self {
+            Self::Capitalized | Self::Title => parts.iter().map(|p| capitalize(p)).collect(),
+            Self::Upper => parts.iter().map(|p| p.to_uppercase()).collect(),
+            Self::Camel => parts
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    if i == 0 {
+                        p.to_lowercase()
+                    } else {
+                        capitalize(p)
+                    }
+                })
+                .collect(),
+            Self::Snake => parts
+                .iter()
+                .map(|p| p.to_lowercase())
+                .collect::<Vec<_>>()
+                .join("_"),
+            Self::Screaming => parts
+                .iter()
+                .map(|p| p.to_uppercase())
+                .collect::<Vec<_>>()
+                .join("_"),
+        }
+    }
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match &*s.to_lowercase() {
+            "capitalized" => Self::Capitalized,
+            "upper" => Self::Upper,
+            "title" => Self::Title,
+            "camel" => Self::Camel,
+            "snake" => Self::Snake,
+            "screaming" => Self::Screaming,
+            _ => return Err(format!("Unknown capitalization style '{}'", s)),
+        })
+    }
+}
+
+/// Uppercases the first character and lowercases the rest.
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
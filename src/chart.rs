@@ -0,0 +1,370 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use crate::{result::TestResult, stats::mean, trend::civil_date};
+
+const DAY: u64 = 24 * 60 * 60;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Settings for charting past results.
+pub struct ChartOptions {
+    pub style: Style,
+    pub period: Period,
+    pub metric: Metric,
+    /// Number of most recent points to show.
+    pub last: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// Points joined by dotted lines, one point per character cell.
+    Line,
+    /// A line drawn with braille dots, for four times the resolution of a line chart.
+    Braille,
+    /// A single row of block characters.
+    Sparkline,
+}
+
+impl FromStr for Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "line" => Ok(Self::Line),
+            "braille" => Ok(Self::Braille),
+            "sparkline" => Ok(Self::Sparkline),
+            _ => Err(format!(
+                "Invalid chart style '{}': must be 'line', 'braille' or 'sparkline'",
+                s
+            )),
+        }
+    }
+}
+
+/// What each point of a chart stands for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Period {
+    /// A single test.
+    Test,
+    /// The average of a week's tests, from Monday.
+    Week,
+    /// The average of a calendar month's tests.
+    Month,
+}
+
+impl FromStr for Period {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "test" | "tests" => Ok(Self::Test),
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            _ => Err(format!(
+                "Invalid chart period '{}': must be 'tests', 'week' or 'month'",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    Wpm,
+    Acc,
+}
+
+impl Metric {
+    fn of(self, result: &TestResult) -> f32 {
+        match self {
+            Self::Wpm => result.wpm,
+            Self::Acc => result.acc,
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "wpm" => Ok(Self::Wpm),
+            "acc" | "accuracy" => Ok(Self::Acc),
+            _ => Err(format!("Invalid metric '{}': must be 'wpm' or 'acc'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wpm => write!(f, "WPM"),
+            Self::Acc => write!(f, "accuracy"),
+        }
+    }
+}
+
+/// Prints a chart of results, oldest first, fitting `width` columns. Quit tests are left out.
+pub fn print_chart(results: &[TestResult], options: &ChartOptions, width: usize) {
+    let mut points = points(results, options.period, options.metric);
+    points.drain(..points.len().saturating_sub(options.last));
+    if points.is_empty() {
+        println!("No finished tests to chart.");
+        return;
+    }
+
+    let title = match options.period {
+        Period::Test => format!("{} per test", options.metric),
+        Period::Week => format!("Weekly average {}", options.metric),
+        Period::Month => format!("Monthly average {}", options.metric),
+    };
+    println!("{}", title);
+
+    let lines = match options.style {
+        Style::Sparkline => sparkline(&points, width),
+        Style::Line | Style::Braille => plot(&points, options.style, width, 12),
+    };
+    for line in lines {
+        println!("{}", line);
+    }
+}
+
+/// A value with the date it was reached on or the period it covers.
+struct Point {
+    label: String,
+    value: f32,
+}
+
+fn points(results: &[TestResult], period: Period, metric: Metric) -> Vec<Point> {
+    let finished = results.iter().filter(|r| !r.quit);
+    let day = |result: &TestResult| (result.timestamp / DAY) as i64;
+
+    if period == Period::Test {
+        return finished
+            .map(|r| Point {
+                label: civil_date(day(r)),
+                value: metric.of(r),
+            })
+            .collect();
+    }
+
+    // Periods are keyed by their first day, so they sort in order.
+    let mut periods: BTreeMap<i64, Vec<f32>> = BTreeMap::new();
+    for result in finished {
+        let day = day(result);
+        let start = match period {
+            // The epoch was a Thursday.
+            Period::Week => (day + 3).div_euclid(7) * 7 - 3,
+            Period::Month => day - civil_date(day)[8..].parse::<i64>().unwrap() + 1,
+            Period::Test => unreachable!(),
+        };
+        periods.entry(start).or_default().push(metric.of(result));
+    }
+    periods
+        .into_iter()
+        .map(|(start, values)| {
+            let date = civil_date(start);
+            Point {
+                label: match period {
+                    Period::Month => date[..7].into(),
+                    _ => date,
+                },
+                value: mean(&values),
+            }
+        })
+        .collect()
+}
+
+/// The range of values shown, padded when all are equal.
+fn bounds(points: &[Point]) -> (f32, f32) {
+    let min = points.iter().map(|p| p.value).fold(f32::INFINITY, f32::min);
+    let max = points
+        .iter()
+        .map(|p| p.value)
+        .fold(f32::NEG_INFINITY, f32::max);
+    if max == min {
+        (min - 1.0, max + 1.0)
+    } else {
+        (min, max)
+    }
+}
+
+fn sparkline(points: &[Point], width: usize) -> Vec<String> {
+    let (min, max) = bounds(points);
+    let labels = [format!("{:.1}", min), format!("{:.1}", max)];
+    let room = width
+        .saturating_sub(labels[0].len() + labels[1].len() + 2)
+        .max(1);
+    let points = &points[points.len().saturating_sub(room)..];
+
+    let sparks: String = points
+        .iter()
+        .map(|p| {
+            let level = (p.value - min) / (max - min) * (SPARKS.len() - 1) as f32;
+            SPARKS[level.round() as usize]
+        })
+        .collect();
+    vec![
+        format!("{} {} {}", labels[0], sparks, labels[1]),
+        format!("{} to {}", points[0].label, points[points.len() - 1].label),
+    ]
+}
+
+/// Plots the points as a line with a labelled axis, `height` rows tall.
+fn plot(points: &[Point], style: Style, width: usize, height: usize) -> Vec<String> {
+    let (min, max) = bounds(points);
+    let labels: Vec<_> = (0..height)
+        .map(|row| {
+            if row == 0 {
+                format!("{:.1}", max)
+            } else if row == height - 1 {
+                format!("{:.1}", min)
+            } else if row == height / 2 {
+                let value = max - (max - min) * row as f32 / (height - 1) as f32;
+                format!("{:.1}", value)
+            } else {
+                String::new()
+            }
+        })
+        .collect();
+    let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+    let columns = width.saturating_sub(label_width + 2).max(1);
+
+    // Dots per character cell.
+    let (dx, dy) = match style {
+        Style::Braille => (2, 4),
+        _ => (1, 1),
+    };
+    let points = &points[points.len().saturating_sub(columns * dx)..];
+    let mut grid = Grid::new(columns * dx, height * dy);
+    let dots: Vec<_> = points
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let x = if points.len() > 1 {
+                i * (grid.width - 1) / (points.len() - 1)
+            } else {
+                0
+            };
+            let y = ((max - p.value) / (max - min) * (grid.height - 1) as f32).round() as usize;
+            (x, y)
+        })
+        .collect();
+    for pair in dots.windows(2) {
+        grid.line(pair[0], pair[1]);
+    }
+    for &(x, y) in &dots {
+        grid.set(x, y, Dot::Point);
+    }
+
+    let mut lines: Vec<_> = labels
+        .iter()
+        .enumerate()
+        .map(|(row, label)| {
+            let cells: String = (0..columns)
+                .map(|column| grid.cell(column, row, style))
+                .collect();
+            format!("{:>w$} ┤{}", label, cells.trim_end(), w = label_width)
+        })
+        .collect();
+    lines.push(format!(
+        "{:>w$} └{}",
+        "",
+        "─".repeat(columns),
+        w = label_width
+    ));
+
+    let first = &points[0].label;
+    let last = &points[points.len() - 1].label;
+    let mut dates = format!("{:>w$}  {}", "", first, w = label_width);
+    if points.len() > 1 {
+        let gap = columns.saturating_sub(first.len() + last.len()).max(1);
+        dates.push_str(&" ".repeat(gap));
+        dates.push_str(last);
+    }
+    lines.push(dates);
+    lines
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Dot {
+    Empty,
+    Line,
+    Point,
+}
+
+struct Grid {
+    width: usize,
+    height: usize,
+    dots: Vec<Dot>,
+}
+
+impl Grid {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            dots: vec![Dot::Empty; width * height],
+        }
+    }
+
+    fn set(&mut self, x: usize, y: usize, dot: Dot) {
+        self.dots[y * self.width + x] = dot;
+    }
+
+    fn get(&self, x: usize, y: usize) -> Dot {
+        self.dots[y * self.width + x]
+    }
+
+    /// Draws a line between two dots with Bresenham's algorithm.
+    fn line(&mut self, (x0, y0): (usize, usize), (x1, y1): (usize, usize)) {
+        let (mut x, mut y) = (x0 as isize, y0 as isize);
+        let (x1, y1) = (x1 as isize, y1 as isize);
+        let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+        let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+        let mut err = dx + dy;
+        loop {
+            self.set(x as usize, y as usize, Dot::Line);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    /// The character showing the dots of a cell.
+    fn cell(&self, column: usize, row: usize, style: Style) -> char {
+        match style {
+            Style::Braille => {
+                // Bits of the dots in a braille cell, by row then column.
+                const BITS: [[u32; 2]; 4] =
+                    [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+                let mut bits = 0;
+                for (dy, row_bits) in BITS.iter().enumerate() {
+                    for (dx, bit) in row_bits.iter().enumerate() {
+                        if self.get(column * 2 + dx, row * 4 + dy) != Dot::Empty {
+                            bits |= bit;
+                        }
+                    }
+                }
+                if bits == 0 {
+                    ' '
+                } else {
+                    char::from_u32(0x2800 + bits).unwrap()
+                }
+            }
+            _ => match self.get(column, row) {
+                Dot::Empty => ' ',
+                Dot::Line => '·',
+                Dot::Point => '●',
+            },
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    mem,
+};
+
+/// Dead-key style sequences for typing characters the keyboard lacks, e.g. `'` then `e` for `é`.
+#[derive(Clone, Debug)]
+pub struct Compose {
+    /// Characters by the keystrokes that type them.
+    pub sequences: HashMap<String, char>,
+}
+
+impl Default for Compose {
+    fn default() -> Self {
+        const ACCENTS: &[(char, &str, &str)] = &[
+            ('\'', "aeiouyc", "áéíóúýć"),
+            ('`', "aeiou", "àèìòù"),
+            ('^', "aeiou", "âêîôû"),
+            ('"', "aeiouy", "äëïöüÿ"),
+            ('~', "nao", "ñãõ"),
+            (',', "c", "ç"),
+        ];
+
+        let mut sequences = HashMap::new();
+        for &(mark, bases, accented) in ACCENTS {
+            let upper_bases = bases.to_uppercase();
+            let upper_accented = accented.to_uppercase();
+            let pairs = bases.chars().zip(accented.chars());
+            let upper_pairs = upper_bases.chars().zip(upper_accented.chars());
+            for (base, accented) in pairs.chain(upper_pairs) {
+                sequences.insert(format!("{}{}", mark, base), accented);
+            }
+        }
+        sequences.insert("\"s".into(), 'ß');
+        sequences.insert("/o".into(), 'ø');
+        sequences.insert("/O".into(), 'Ø');
+        Self { sequences }
+    }
+}
+
+impl Compose {
+    /// Reads a `[compose]` table, with a `sequences` table mapping keystrokes to the character
+    /// they type. Sequences given here are added to the built-in ones, and an empty string
+    /// removes a built-in sequence.
+    pub fn from_value(value: &toml::Value) -> Result<Self, String> {
+        let mut compose = Self::default();
+
+        if let Some(sequences) = value.get("sequences") {
+            let table = sequences
+                .as_table()
+                .ok_or_else(|| "'sequences' must be a table".to_string())?;
+            for (keys, output) in table {
+                let error = || format!("Invalid sequence '{}': must type one character", keys);
+                if keys.chars().count() < 2 {
+                    return Err(format!(
+                        "Invalid sequence '{}': must be at least two keystrokes",
+                        keys
+                    ));
+                }
+                let output = output.as_str().ok_or_else(error)?;
+                let mut chars = output.chars();
+                match (chars.next(), chars.next()) {
+                    (None, _) => {
+                        compose.sequences.remove(keys);
+                    }
+                    (Some(c), None) => {
+                        compose.sequences.insert(keys.clone(), c);
+                    }
+                    _ => return Err(error()),
+                }
+            }
+        }
+
+        Ok(compose)
+    }
+}
+
+/// Turns keystrokes into characters using the compose sequences.
+pub struct Composer {
+    compose: Compose,
+    /// Keystrokes typed so far of a possible sequence.
+    pending: String,
+}
+
+impl Composer {
+    pub fn new(compose: Compose) -> Self {
+        Self {
+            compose,
+            pending: String::new(),
+        }
+    }
+
+    /// Feeds a keystroke, returning the characters it completes, each with the keystrokes that
+    /// typed it.
+    ///
+    /// Keystrokes that may start a sequence are held back until the sequence is complete or
+    /// can no longer match, at which point they are typed as themselves. Typing such a
+    /// keystroke twice types it once as itself, so `''` then `a` types `'a` rather than `'á`.
+    pub fn feed(&mut self, c: char) -> Vec<(char, String)> {
+        let mut typed = Vec::new();
+        let mut queue: VecDeque<_> = self.pending.drain(..).chain([c]).collect();
+
+        while let Some(c) = queue.pop_front() {
+            self.pending.push(c);
+            if let Some(&composed) = self.compose.sequences.get(&self.pending) {
+                typed.push((composed, mem::take(&mut self.pending)));
+            } else if self.is_prefix(&self.pending) {
+                // Wait for the rest of the sequence.
+                continue;
+            } else if is_doubled(&self.pending) {
+                typed.push((c, mem::take(&mut self.pending)));
+            } else {
+                // The first keystroke is typed as is, and the rest may start another sequence.
+                let pending = mem::take(&mut self.pending);
+                let mut keys = pending.chars();
+                if let Some(first) = keys.next() {
+                    typed.push((first, first.to_string()));
+                }
+                for c in keys.rev() {
+                    queue.push_front(c);
+                }
+            }
+        }
+
+        typed
+    }
+
+    /// Drops any held keystrokes, returning whether there were any.
+    pub fn cancel(&mut self) -> bool {
+        let pending = !self.pending.is_empty();
+        self.pending.clear();
+        pending
+    }
+
+    fn is_prefix(&self, keys: &str) -> bool {
+        self.compose
+            .sequences
+            .keys()
+            .any(|seq| seq.starts_with(keys))
+    }
+}
+
+/// Whether the keys are one keystroke typed twice.
+fn is_doubled(keys: &str) -> bool {
+    let mut chars = keys.chars();
+    matches!((chars.next(), chars.next(), chars.next()), (Some(a), Some(b), None) if a == b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(keys: &str) -> String {
+        let mut composer = Composer::new(Compose::default());
+        keys.chars()
+            .flat_map(|c| composer.feed(c))
+            .map(|(c, _)| c)
+            .collect()
+    }
+
+    #[test]
+    fn doubled_marks_type_themselves() {
+        assert_eq!(typed("'e"), "é");
+        assert_eq!(typed("d''abord"), "d'abord");
+        assert_eq!(typed("'''a"), "'á");
+        assert_eq!(typed(",,c"), ",c");
+        assert_eq!(typed("a' "), "a' ");
+    }
+}
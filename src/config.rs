@@ -1,18 +1,45 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     env, fmt, fs, io,
     path::{Path, PathBuf},
 };
 
-use crate::theme::{Theme, ThemeError, Themes};
+use crate::{
+    caps::Caps,
+    compose::Compose,
+    layout::Layout,
+    lazy::Lazy,
+    markov,
+    numbers::Numbers,
+    punct,
+    symbols::Symbols,
+    theme::{Theme, ThemeError, Themes},
+    words::{Metadata, SetSource, BUILTIN_SETS},
+};
 
 #[derive(Debug)]
 pub struct Config {
     pub db_path: PathBuf,
-    pub sets: HashMap<String, PathBuf>,
+    /// Word set directories, with sets in later directories replacing those of the same name in
+    /// earlier ones. New sets are written to the first.
+    pub sets_dirs: Vec<PathBuf>,
+    pub sets: HashMap<String, SetSource>,
     pub theme: Theme,
     pub themes: Themes,
     pub show_bg: bool,
+    /// Punctuation rules by language tag, with `default` applying to all languages.
+    pub punctuation: HashMap<String, punct::Overrides>,
+    pub numbers: Numbers,
+    pub symbols: Symbols,
+    pub caps: Caps,
+    pub lazy: Lazy,
+    pub compose: Compose,
+    /// Name of the layout to emulate.
+    pub layout: Option<String>,
+    /// Custom layouts by name.
+    pub layouts: HashMap<String, Layout>,
+    /// Problems that didn't stop the config from loading, such as unreadable word set files.
+    pub warnings: Vec<String>,
 }
 
 impl Config {
@@ -21,7 +48,15 @@ impl Config {
             .map_err(|e| ConfigError::Read(e.to_string()))?
             .parse::<toml::Value>()
             .map_err(|e| ConfigError::Toml(e.to_string()))?;
+        Self::from_value(&value)
+    }
+
+    /// The configuration used without a config file, with every setting at its default.
+    pub fn builtin() -> Result<Self, ConfigError> {
+        Self::from_value(&toml::Value::Table(toml::value::Table::new()))
+    }
 
+    fn from_value(value: &toml::Value) -> Result<Self, ConfigError> {
         let db_path = match value.get("db").and_then(|v| v.as_str()) {
             Some(path) => path.into(),
             None => default_data_dir()
@@ -29,21 +64,44 @@ impl Config {
                 .ok_or(ConfigError::NoDatabase)?,
         };
 
-        let sets_dir = match value.get("sets_dir").and_then(|v| v.as_str()) {
-            Some(path) => path.into(),
-            None => default_data_dir()
-                .map(|dir| dir.join("sets"))
-                .ok_or(ConfigError::NoSetsDir)?,
+        // The default directory need not exist, as the built-in sets are always available.
+        let mut optional_dir = false;
+        let sets_dirs: Vec<PathBuf> = match value.get("sets_dir") {
+            Some(toml::Value::String(path)) => vec![path.into()],
+            Some(toml::Value::Array(paths)) => paths
+                .iter()
+                .map(|path| path.as_str().map(PathBuf::from))
+                .collect::<Option<_>>()
+                .ok_or(ConfigError::SetsDirType)?,
+            Some(_) => return Err(ConfigError::SetsDirType),
+            None => {
+                optional_dir = true;
+                vec![default_sets_dir().ok_or(ConfigError::NoSetsDir)?]
+            }
         };
-
-        if !sets_dir.exists() || !sets_dir.is_dir() {
-            return Err(ConfigError::InvalidSetsDir(sets_dir));
+        if sets_dirs.is_empty() {
+            return Err(ConfigError::NoSetsDir);
         }
 
-        let sets =
-            collect_word_sets(&sets_dir).map_err(|e| ConfigError::CollectSets(e.to_string()))?;
+        let mut sets: HashMap<_, _> = BUILTIN_SETS
+            .iter()
+            .map(|(name, _)| (name.to_string(), SetSource::Builtin(name)))
+            .collect();
+        let mut warnings = Vec::new();
+        for dir in &sets_dirs {
+            if optional_dir && !dir.exists() {
+                continue;
+            }
+            if !dir.is_dir() {
+                return Err(ConfigError::InvalidSetsDir(dir.clone()));
+            }
+            collect_word_sets(dir, &mut sets, &mut warnings)
+                .map_err(|e| ConfigError::CollectSets(e.to_string()))?;
+        }
+        sets.extend(collect_composite_sets(value)?);
+        sets.extend(collect_markov_sets(value)?);
 
-        let themes = Themes::from_value(&value).map_err(ConfigError::Theme)?;
+        let themes = Themes::from_value(value).map_err(ConfigError::Theme)?;
         let theme_name = value.get("theme").and_then(|v| v.as_str());
         let theme = match theme_name {
             Some(name) => themes
@@ -57,33 +115,376 @@ impl Config {
             .and_then(|v| v.as_bool())
             .unwrap_or(true);
 
+        let punctuation = match value.get("punctuation") {
+            Some(table) => table
+                .as_table()
+                .ok_or_else(|| ConfigError::Punctuation("must be a table".into()))?
+                .iter()
+                .map(|(language, rules)| {
+                    punct::Overrides::from_value(rules)
+                        .map(|rules| (language.clone(), rules))
+                        .map_err(|e| ConfigError::Punctuation(format!("[{}]: {}", language, e)))
+                })
+                .collect::<Result<_, _>>()?,
+            None => HashMap::new(),
+        };
+
+        let numbers = match value.get("numbers") {
+            Some(numbers) => Numbers::from_value(numbers).map_err(ConfigError::Numbers)?,
+            None => Numbers::default(),
+        };
+
+        let symbols = match value.get("symbols") {
+            Some(symbols) => Symbols::from_value(symbols).map_err(ConfigError::Symbols)?,
+            None => Symbols::default(),
+        };
+
+        let caps = match value.get("caps") {
+            Some(caps) => Caps::from_value(caps).map_err(ConfigError::Caps)?,
+            None => Caps::default(),
+        };
+
+        let lazy = match value.get("lazy") {
+            Some(lazy) => Lazy::from_value(lazy).map_err(ConfigError::Lazy)?,
+            None => Lazy::default(),
+        };
+
+        let compose = match value.get("compose") {
+            Some(compose) => Compose::from_value(compose).map_err(ConfigError::Compose)?,
+            None => Compose::default(),
+        };
+
+        let layouts = match value.get("layouts") {
+            Some(table) => table
+                .as_table()
+                .ok_or_else(|| ConfigError::Layout("layouts".into(), "must be a table".into()))?
+                .iter()
+                .map(|(name, layout)| {
+                    Layout::from_value(layout)
+                        .map(|layout| (name.clone(), layout))
+                        .map_err(|e| ConfigError::Layout(name.clone(), e))
+                })
+                .collect::<Result<_, _>>()?,
+            None => HashMap::new(),
+        };
+
+        let layout = value
+            .get("layout")
+            .and_then(|v| v.as_str())
+            .map(String::from);
+        if let Some(name) = &layout {
+            if !layouts.contains_key(name) && Layout::builtin(name).is_none() {
+                return Err(ConfigError::NoLayout(name.clone()));
+            }
+        }
+
         Ok(Self {
             db_path,
+            sets_dirs,
             sets,
             theme,
             themes,
             show_bg,
+            punctuation,
+            numbers,
+            symbols,
+            caps,
+            lazy,
+            compose,
+            layout,
+            layouts,
+            warnings,
         })
     }
+
+    /// Punctuation rules for a word set, from the most general to the most specific: built-in
+    /// rules for its language, `default` and language rules from the config, then the set's own.
+    pub fn punct_rules(&self, meta: &Metadata) -> punct::Rules {
+        let language = meta.language.as_deref();
+        let mut rules = punct::Rules::for_language(language);
+
+        let mut keys = vec!["default"];
+        if let Some(language) = language {
+            if let Some(primary) = language.split(['-', '_']).next() {
+                keys.push(primary);
+            }
+            if !keys.contains(&language) {
+                keys.push(language);
+            }
+        }
+        for key in keys {
+            if let Some(overrides) = self.punctuation.get(key) {
+                rules.merge(overrides);
+            }
+        }
+
+        if let Some(overrides) = &meta.punctuation {
+            rules.merge(overrides);
+        }
+        rules
+    }
+
+    /// A layout by name, from the custom layouts or the built-in ones.
+    pub fn get_layout(&self, name: &str) -> Option<Layout> {
+        self.layouts
+            .get(name)
+            .cloned()
+            .or_else(|| Layout::builtin(name))
+    }
 }
 
-fn collect_word_sets(sets_dir: &Path) -> io::Result<HashMap<String, PathBuf>> {
-    let mut sets = HashMap::new();
+/// Adds the word sets found in `sets_dir` and its subdirectories, replacing any of the same name.
+///
+/// Sets are named by their path relative to `sets_dir` without the extension, so
+/// `code/rust.txt` becomes `code/rust`. Hidden files are ignored, and entries that can't be read,
+/// such as broken symlinks, are skipped with a warning.
+fn collect_word_sets(
+    sets_dir: &Path,
+    sets: &mut HashMap<String, SetSource>,
+    warnings: &mut Vec<String>,
+) -> io::Result<()> {
+    let mut found = HashMap::new();
+    let mut visited = HashSet::new();
+    let mut pending = vec![(sets_dir.canonicalize()?, String::new())];
+
+    while let Some((dir, prefix)) = pending.pop() {
+        // Guards against symlinks looping back to a parent directory.
+        if !visited.insert(dir.clone()) {
+            continue;
+        }
 
-    for entry in sets_dir.read_dir()? {
-        let path = entry?.path();
-        let path = path.canonicalize()?;
+        let entries = match dir.read_dir() {
+            Ok(entries) => entries,
+            Err(e) => {
+                warnings.push(format!("skipping directory '{}': {}", dir.display(), e));
+                continue;
+            }
+        };
+        for entry in entries {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    warnings.push(format!("skipping entry in '{}': {}", dir.display(), e));
+                    continue;
+                }
+            };
+            if path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+            {
+                continue;
+            }
 
-        let stem = path
-            .file_stem()
+            let target = match path.canonicalize() {
+                Ok(target) => target,
+                Err(e) => {
+                    warnings.push(format!("skipping '{}': {}", path.display(), e));
+                    continue;
+                }
+            };
+            let is_dir = target.is_dir();
+            let stem = match if is_dir {
+                path.file_name()
+            } else {
+                path.file_stem()
+            }
             .and_then(|s| s.to_str())
-            .expect("Fatal: word set stem is invalid UTF-8");
-        sets.insert(stem.to_string(), path);
+            {
+                Some(stem) => stem,
+                None => {
+                    warnings.push(format!(
+                        "skipping '{}': name is not valid UTF-8",
+                        path.display()
+                    ));
+                    continue;
+                }
+            };
+            let name = format!("{}{}", prefix, stem);
+
+            if is_dir {
+                pending.push((target, format!("{}/", name)));
+            } else if let Some(other) = found.insert(name.clone(), path.clone()) {
+                warnings.push(format!(
+                    "word set '{}' is defined by both '{}' and '{}'",
+                    name,
+                    other.display(),
+                    path.display()
+                ));
+            } else {
+                sets.insert(name, SetSource::File(target));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads composite sets from `[composites.<name>]` tables mapping set names to weights.
+fn collect_composite_sets(value: &toml::Value) -> Result<HashMap<String, SetSource>, ConfigError> {
+    let table = match value.get("composites") {
+        Some(table) => table
+            .as_table()
+            .ok_or_else(|| ConfigError::Composite("composites".into()))?,
+        None => return Ok(HashMap::new()),
+    };
+
+    let mut sets = HashMap::new();
+    for (name, parts) in table {
+        let parts =
+            parse_weights(parts, name, "set").map_err(|_| ConfigError::Composite(name.clone()))?;
+        if !parts.iter().any(|(_, w)| *w > 0.0) {
+            return Err(ConfigError::CompositeWeights(name.clone()));
+        }
+        sets.insert(name.clone(), SetSource::Composite(parts));
     }
 
     Ok(sets)
 }
 
+/// Reads a number given as a float or an integer.
+pub fn as_number(value: &toml::Value) -> Option<f64> {
+    value
+        .as_float()
+        .or_else(|| value.as_integer().map(|i| i as f64))
+}
+
+/// Reads a number between 0 and 1, such as a probability or a fraction of words.
+pub fn as_fraction(value: &toml::Value) -> Option<f64> {
+    as_number(value).filter(|f| (0.0..=1.0).contains(f))
+}
+
+/// Reads a number between 0 and 1, with an error naming it as `key`.
+pub fn parse_fraction(value: &toml::Value, key: &str) -> Result<f64, String> {
+    as_fraction(value).ok_or_else(|| format!("'{}' must be a number between 0 and 1", key))
+}
+
+/// Reads a table of relative weights, each a finite, non-negative number. Errors name the table
+/// as `key` and its entries as `item`s.
+pub fn parse_weights(
+    value: &toml::Value,
+    key: &str,
+    item: &str,
+) -> Result<Vec<(String, f64)>, String> {
+    value
+        .as_table()
+        .ok_or_else(|| format!("'{}' must be a table of weights", key))?
+        .iter()
+        .map(|(name, weight)| {
+            as_number(weight)
+                .filter(|w| w.is_finite() && *w >= 0.0)
+                .map(|w| (name.clone(), w))
+                .ok_or_else(|| format!("Invalid weight for {} '{}'", item, name))
+        })
+        .collect()
+}
+
+/// Reads generated sets from `[markov.<name>]` tables, each giving a `corpus` path and optionally
+/// the model `level` ("word" or "char") and n-gram `order`.
+fn collect_markov_sets(value: &toml::Value) -> Result<HashMap<String, SetSource>, ConfigError> {
+    let table = match value.get("markov") {
+        Some(table) => table
+            .as_table()
+            .ok_or_else(|| ConfigError::Markov("markov".into(), "must be a table".into()))?,
+        None => return Ok(HashMap::new()),
+    };
+
+    let mut sets = HashMap::new();
+    for (name, set) in table {
+        let error = |e: &str| ConfigError::Markov(name.clone(), e.into());
+
+        let corpus = set
+            .get("corpus")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| error("missing 'corpus' path"))?;
+        let level = match set.get("level") {
+            Some(level) => level
+                .as_str()
+                .ok_or_else(|| error("'level' must be a string"))?
+                .parse()
+                .map_err(|e: String| error(&e))?,
+            None => markov::Level::Word,
+        };
+        let order = match set.get("order") {
+            Some(order) => order
+                .as_integer()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| error("'order' must be a positive integer"))?
+                as usize,
+            None => level.default_order(),
+        };
+
+        sets.insert(
+            name.clone(),
+            SetSource::Markov {
+                corpus: corpus.into(),
+                level,
+                order,
+            },
+        );
+    }
+
+    Ok(sets)
+}
+
+/// The commented config written by `--init`.
+const STARTER_CONFIG: &str = include_str!("../config.toml");
+
+/// Writes the starter config and the built-in word sets to the default directories, returning the
+/// paths written.
+///
+/// An existing config is only replaced if `force` is set, and existing word sets are kept.
+pub fn init(force: bool) -> Result<Vec<PathBuf>, InitError> {
+    let config_path = default_config_dir()
+        .map(|dir| dir.join("config.toml"))
+        .ok_or(InitError::NoDir)?;
+    let sets_dir = default_sets_dir().ok_or(InitError::NoDir)?;
+
+    if config_path.exists() && !force {
+        return Err(InitError::Exists(config_path));
+    }
+
+    let write = |path: &Path, contents: &str| {
+        path.parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(path, contents))
+            .map_err(|e| InitError::Write(path.into(), e.to_string()))
+    };
+
+    let mut written = Vec::new();
+    write(&config_path, STARTER_CONFIG)?;
+    written.push(config_path);
+    for (name, contents) in BUILTIN_SETS {
+        let path = sets_dir.join(name);
+        if !path.exists() {
+            write(&path, contents)?;
+            written.push(path);
+        }
+    }
+
+    Ok(written)
+}
+
+#[derive(Debug)]
+pub enum InitError {
+    NoDir,
+    Exists(PathBuf),
+    Write(PathBuf, String),
+}
+
+impl fmt::Display for InitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoDir => write!(f, "Could not find the config and data directories"),
+            Self::Exists(path) => write!(
+                f,
+                "Config '{}' already exists (use --force to overwrite)",
+                path.display()
+            ),
+            Self::Write(path, e) => write!(f, "Failed to write '{}': {}", path.display(), e),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ConfigError {
     Read(String),
@@ -92,8 +493,20 @@ pub enum ConfigError {
     NoTheme(String),
     NoDatabase,
     NoSetsDir,
+    SetsDirType,
     InvalidSetsDir(PathBuf),
     CollectSets(String),
+    Composite(String),
+    CompositeWeights(String),
+    Markov(String, String),
+    Punctuation(String),
+    Numbers(String),
+    Symbols(String),
+    Caps(String),
+    Lazy(String),
+    Compose(String),
+    Layout(String, String),
+    NoLayout(String),
 }
 
 impl fmt::Display for ConfigError {
@@ -105,10 +518,33 @@ impl fmt::Display for ConfigError {
             Self::NoTheme(theme) => write!(f, "No theme '{}' found", theme),
             Self::NoDatabase => write!(f, "Database not specified"),
             Self::NoSetsDir => write!(f, "No word set directory specified"),
+            Self::SetsDirType => write!(
+                f,
+                "'sets_dir' must be a path or a list of paths"
+            ),
             Self::InvalidSetsDir(path) => {
                 write!(f, "Invalid word set directory '{}'", path.display())
             }
             Self::CollectSets(e) => write!(f, "Failed to read sets: {}", e),
+            Self::Caps(e) => write!(f, "Invalid capitalization settings: {}", e),
+            Self::Lazy(e) => write!(f, "Invalid lazy mode settings: {}", e),
+            Self::Compose(e) => write!(f, "Invalid compose settings: {}", e),
+            Self::Layout(name, e) => write!(f, "Invalid layout '{}': {}", name, e),
+            Self::NoLayout(name) => write!(f, "No layout '{}' found", name),
+            Self::Symbols(e) => write!(f, "Invalid symbol settings: {}", e),
+            Self::Numbers(e) => write!(f, "Invalid number settings: {}", e),
+            Self::Punctuation(e) => write!(f, "Invalid punctuation rules: {}", e),
+            Self::Markov(name, e) => write!(f, "Invalid generated set '{}': {}", name, e),
+            Self::Composite(name) => write!(
+                f,
+                "Invalid composite set '{}': must be formatted as [composites.<name>] with set = weight entries",
+                name
+            ),
+            Self::CompositeWeights(name) => write!(
+                f,
+                "Invalid composite set '{}': at least one weight must be above 0",
+                name
+            ),
         }
     }
 }
@@ -121,6 +557,10 @@ pub fn default_data_dir() -> Option<PathBuf> {
     xdg_data().map(|dir| dir.join("typre"))
 }
 
+pub fn default_sets_dir() -> Option<PathBuf> {
+    default_data_dir().map(|dir| dir.join("sets"))
+}
+
 #[cfg(unix)]
 fn xdg_config() -> Option<PathBuf> {
     Some(match env::var("XDG_CONFIG_HOME") {
@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    io::{self, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::words::Filter;
+
+/// Settings for building a word set from a corpus.
+pub struct BuildOptions {
+    /// Number of most frequent words to keep.
+    pub top: usize,
+    /// Whether to lowercase words before counting.
+    pub fold_case: bool,
+    pub filter: Filter,
+}
+
+/// Builds a word set named `name` in `sets_dir` from a corpus file.
+pub fn build_set(
+    corpus: &Path,
+    sets_dir: &Path,
+    name: &str,
+    force: bool,
+    options: &BuildOptions,
+) -> Result<(PathBuf, usize), BuildError> {
+    // The set must stay inside the sets directory.
+    let inside = Path::new(name)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !inside || name.trim().is_empty() {
+        return Err(BuildError::InvalidName(name.into()));
+    }
+
+    let out = sets_dir.join(name);
+    if out.exists() && !force {
+        return Err(BuildError::Exists(out));
+    }
+
+    let bytes = fs::read(corpus).map_err(|e| BuildError::Read(corpus.into(), e.to_string()))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let counts = count_words(&text, options);
+    if counts.is_empty() {
+        return Err(BuildError::Empty(corpus.into()));
+    }
+
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent).map_err(|e| BuildError::Write(out.clone(), e.to_string()))?;
+    }
+    write_set(&out, name, corpus, &counts)?;
+    Ok((out, counts.len()))
+}
+
+/// Counts the words of a corpus file, returning the `top` most frequent words and their counts.
+///
+/// Words are split at Unicode word boundaries, so apostrophes stay inside words (e.g. `don't`).
+/// Ties are broken alphabetically so the output is stable.
+fn count_words(text: &str, options: &BuildOptions) -> Vec<(String, u64)> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for word in tokenize(text) {
+        let word = if options.fold_case {
+            word.to_lowercase()
+        } else {
+            word.to_string()
+        };
+        if options.filter.matches(&word) {
+            *counts.entry(word).or_default() += 1;
+        }
+    }
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_unstable_by(|(a, m), (b, n)| n.cmp(m).then_with(|| a.cmp(b)));
+    counts.truncate(options.top);
+    counts
+}
+
+/// Splits text into words at Unicode word boundaries, dropping numbers and punctuation.
+fn tokenize(text: &str) -> impl Iterator<Item = &str> {
+    text.unicode_words()
+        .filter(|word| word.chars().any(char::is_alphabetic))
+}
+
+/// Writes a word set file with a metadata header and a weight column.
+fn write_set(
+    path: &Path,
+    name: &str,
+    corpus: &Path,
+    counts: &[(String, u64)],
+) -> Result<(), BuildError> {
+    let write = || -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        writeln!(file, "+++")?;
+        let description = format!(
+            "The {} most frequent words of '{}'.",
+            counts.len(),
+            corpus.display()
+        );
+        writeln!(file, "name = {}", toml::Value::from(name))?;
+        writeln!(file, "description = {}", toml::Value::from(description))?;
+        writeln!(file, "+++")?;
+        for (word, count) in counts {
+            writeln!(file, "{}\t{}", word, count)?;
+        }
+        file.flush()
+    };
+    write().map_err(|e| BuildError::Write(path.into(), e.to_string()))
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    Read(PathBuf, String),
+    Write(PathBuf, String),
+    Exists(PathBuf),
+    Empty(PathBuf),
+    InvalidName(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(path, e) => write!(f, "Failed to read corpus '{}': {}", path.display(), e),
+            Self::Write(path, e) => {
+                write!(f, "Failed to write word set '{}': {}", path.display(), e)
+            }
+            Self::Exists(path) => write!(
+                f,
+                "Word set '{}' already exists (use --force to overwrite)",
+                path.display()
+            ),
+            Self::Empty(path) => write!(
+                f,
+                "No words in corpus '{}' match the filters",
+                path.display()
+            ),
+            Self::InvalidName(name) => write!(
+                f,
+                "Invalid word set name '{}': must be a relative path inside the sets directory",
+                name
+            ),
+        }
+    }
+}
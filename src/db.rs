@@ -1,19 +1,22 @@
-use std::{ops::Range, path::Path};
+use std::{
+    fmt, fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
-use rusqlite::{params, Connection};
+use rusqlite::{params, params_from_iter, Connection, ToSql, Transaction};
 
-use crate::result::TestResult;
+use crate::result::{Mode, TestResult};
 
-pub struct Db {
-    conn: Connection,
-}
+type Migration = fn(&Transaction) -> Result<(), rusqlite::Error>;
 
-impl Db {
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, rusqlite::Error> {
-        let conn = Connection::open(path)?;
-        conn.execute_batch(
-            r#"BEGIN;
-               CREATE TABLE IF NOT EXISTS results (
+/// Schema changes in order, with the schema version after migration `i` being `i + 1`.
+///
+/// Released migrations must never be edited; changes to the schema go in a new one.
+const MIGRATIONS: &[Migration] = &[
+    |tx| {
+        tx.execute_batch(
+            r#"CREATE TABLE IF NOT EXISTS results (
                  id INTEGER PRIMARY KEY,
                  timestamp INTEGER NOT NULL,
                  duration INTEGER NOT NULL,
@@ -27,83 +30,597 @@ impl Db {
                  errors INTEGER NOT NULL,
                  quit INTEGER NOT NULL,
                  history BLOB NOT NULL
+               );"#,
+        )
+    },
+    |tx| {
+        // Unversioned databases may already have the column.
+        let has_lazy = tx
+            .prepare("SELECT 1 FROM pragma_table_info('results') WHERE name = 'lazy'")?
+            .exists([])?;
+        if !has_lazy {
+            tx.execute(
+                "ALTER TABLE results ADD COLUMN lazy INTEGER NOT NULL DEFAULT 0",
+                [],
+            )?;
+        }
+        Ok(())
+    },
+    |tx| {
+        tx.execute_batch(
+            r#"ALTER TABLE results ADD COLUMN mode TEXT NOT NULL DEFAULT 'words';
+               CREATE TABLE tags (
+                 result_id INTEGER NOT NULL REFERENCES results (id) ON DELETE CASCADE,
+                 tag TEXT NOT NULL,
+                 PRIMARY KEY (result_id, tag)
                );
-               COMMIT;"#,
-        )?;
+               CREATE INDEX tags_by_tag ON tags (tag);
+               CREATE INDEX results_by_timestamp ON results (timestamp);"#,
+        )
+    },
+    |tx| {
+        tx.execute_batch(
+            r#"ALTER TABLE results ADD COLUMN caps INTEGER NOT NULL DEFAULT 0;
+               ALTER TABLE results ADD COLUMN symbols INTEGER NOT NULL DEFAULT 0;
+               ALTER TABLE results ADD COLUMN filter TEXT;"#,
+        )
+    },
+];
+
+pub struct Db {
+    conn: Connection,
+}
+
+impl Db {
+    /// Opens the database, migrating it to the current schema.
+    ///
+    /// Existing databases are copied next to the original before migrating, and databases
+    /// from a newer version of typre are refused.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, DbError> {
+        let path = path.as_ref();
+        let mut conn = Connection::open(path)?;
+
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > MIGRATIONS.len() {
+            return Err(DbError::TooNew(version));
+        }
+        if version == MIGRATIONS.len() {
+            return Ok(Self { conn });
+        }
+
+        let is_empty = !conn.prepare("SELECT 1 FROM sqlite_master")?.exists([])?;
+        if !is_empty {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(format!(".v{}.bak", version));
+            let backup = PathBuf::from(backup);
+            backup_to(&conn, &backup).map_err(|e| DbError::Backup(backup, e))?;
+        }
+
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = conn.transaction()?;
+            migration(&tx).map_err(|e| DbError::Migration(i + 1, e))?;
+            tx.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+        }
+
         Ok(Self { conn })
     }
 
-    pub fn save_result(&self, result: &TestResult) -> Result<(), rusqlite::Error> {
-        self.conn.execute(
+    pub fn save_result(&self, result: &TestResult) -> Result<(), DbError> {
+        let history = bincode::encode_to_vec(&result.history, bincode::config::standard())
+            .map_err(|e| DbError::Encode(e.to_string()))?;
+
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute(
             "INSERT INTO results (timestamp, duration,
-                                  word_set, word_count,
-                                  punct, numbers,
+                                  word_set, mode, word_count,
+                                  punct, numbers, caps, symbols, filter, lazy,
                                   wpm, acc, cons, errors,
                                   quit,
                                   history)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
             params![
                 result.timestamp,
                 result.duration,
                 result.word_set,
+                result.mode.to_string(),
                 result.word_count,
                 result.punct,
                 result.numbers,
+                result.caps,
+                result.symbols,
+                result.filter,
+                result.lazy,
                 result.wpm,
                 result.acc,
                 result.cons,
                 result.errors,
                 result.quit,
-                bincode::encode_to_vec(&result.history, bincode::config::standard()).unwrap(),
+                history,
             ],
         )?;
+        let id = tx.last_insert_rowid();
+        for tag in &result.tags {
+            tx.execute(
+                "INSERT OR IGNORE INTO tags (result_id, tag) VALUES (?1, ?2)",
+                params![id, tag],
+            )?;
+        }
+        tx.commit()?;
         Ok(())
     }
 
-    pub fn get_results_range(&self, range: Range<u64>) -> Result<Vec<TestResult>, rusqlite::Error> {
-        let mut stmt = self.conn.prepare(
-            "SELECT timestamp,
+    /// The fastest finished result with the same word set, mode, length, punctuation, numbers,
+    /// caps, symbols and word filters as the given one.
+    pub fn personal_best(&self, result: &TestResult) -> Result<Option<TestResult>, DbError> {
+        let query = Query::new()
+            .word_set(&result.word_set)
+            .mode(result.mode)
+            .word_count(result.word_count)
+            .punct(result.punct)
+            .numbers(result.numbers)
+            .caps(result.caps)
+            .symbols(result.symbols)
+            .filter(result.filter.as_deref())
+            .quit(false)
+            .order(Order::Fastest)
+            .limit(1);
+        Ok(self.results(&query)?.pop())
+    }
+
+    /// Results matching a query.
+    pub fn results(&self, query: &Query) -> Result<Vec<TestResult>, DbError> {
+        let mut conditions = Vec::new();
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+        let mut condition = |sql: &str, value: Box<dyn ToSql>| {
+            values.push(value);
+            conditions.push(sql.replace('?', &format!("?{}", values.len())));
+        };
+
+        if let Some(word_set) = &query.word_set {
+            condition("word_set = ?", Box::new(word_set.clone()));
+        }
+        if let Some(mode) = query.mode {
+            condition("mode = ?", Box::new(mode.to_string()));
+        }
+        if let Some(word_count) = query.word_count {
+            condition("word_count = ?", Box::new(word_count));
+        }
+        if let Some(punct) = query.punct {
+            condition("punct = ?", Box::new(punct));
+        }
+        if let Some(numbers) = query.numbers {
+            condition("numbers = ?", Box::new(numbers));
+        }
+        if let Some(caps) = query.caps {
+            condition("caps = ?", Box::new(caps));
+        }
+        if let Some(symbols) = query.symbols {
+            condition("symbols = ?", Box::new(symbols));
+        }
+        if let Some(filter) = &query.filter {
+            // `IS` also matches results without filters when `filter` is `None`.
+            condition("filter IS ?", Box::new(filter.clone()));
+        }
+        if let Some(lazy) = query.lazy {
+            condition("lazy = ?", Box::new(lazy));
+        }
+        if let Some(since) = query.since {
+            condition("timestamp >= ?", Box::new(since));
+        }
+        if let Some(until) = query.until {
+            condition("timestamp < ?", Box::new(until));
+        }
+        if let Some(quit) = query.quit {
+            condition("quit = ?", Box::new(quit));
+        }
+        for tag in &query.tags {
+            condition(
+                "id IN (SELECT result_id FROM tags WHERE tag = ?)",
+                Box::new(tag.clone()),
+            );
+        }
+
+        let mut sql = String::from(
+            "SELECT id,
+                    timestamp,
                     duration,
                     word_set,
+                    mode,
                     word_count,
                     punct,
                     numbers,
+                    caps,
+                    symbols,
+                    filter,
+                    lazy,
                     wpm,
                     acc,
                     cons,
                     errors,
                     quit,
-                    punct,
-                    numbers,
                     history
-             FROM results
-             WHERE timestamp >= ?1 AND timestamp <= ?2",
-        )?;
-        let rows = stmt.query_map([range.start, range.end], |row| {
-            Ok(TestResult {
+             FROM results",
+        );
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(match query.order {
+            Order::Oldest => " ORDER BY timestamp ASC, id ASC",
+            #[cfg(test)]
+            Order::Newest => " ORDER BY timestamp DESC, id DESC",
+            Order::Fastest => " ORDER BY wpm DESC, timestamp ASC, id ASC",
+        });
+        if let Some(limit) = query.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let mut tags_stmt = self
+            .conn
+            .prepare("SELECT tag FROM tags WHERE result_id = ?1 ORDER BY tag")?;
+        let mut rows = stmt.query(params_from_iter(&values))?;
+        let mut results = Vec::new();
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get("id")?;
+
+            let mode: String = row.get("mode")?;
+            let mode = mode.parse().map_err(|e| DbError::Decode(id, e))?;
+            let history: Vec<u8> = row.get("history")?;
+            let (history, _) = bincode::decode_from_slice(&history, bincode::config::standard())
+                .map_err(|e| DbError::Decode(id, format!("invalid history: {}", e)))?;
+            let tags = tags_stmt
+                .query_map([id], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+
+            results.push(TestResult {
                 timestamp: row.get("timestamp")?,
                 duration: row.get("duration")?,
                 word_set: row.get("word_set")?,
+                mode,
                 word_count: row.get("word_count")?,
                 punct: row.get("punct")?,
                 numbers: row.get("numbers")?,
+                caps: row.get("caps")?,
+                symbols: row.get("symbols")?,
+                filter: row.get("filter")?,
+                lazy: row.get("lazy")?,
                 wpm: row.get("wpm")?,
                 acc: row.get("acc")?,
                 cons: row.get("cons")?,
                 errors: row.get("errors")?,
                 quit: row.get("quit")?,
-                history: {
-                    let history: Vec<u8> = row.get("history")?;
-                    bincode::decode_from_slice(&history, bincode::config::standard())
-                        .unwrap()
-                        .0
-                },
-            })
-        })?;
-        let mut results = Vec::new();
-        for result in rows {
-            results.push(result?);
+                tags,
+                history,
+            });
         }
         Ok(results)
     }
 }
+
+/// Filters, ordering and limit for [`Db::results`]. Filters left unset match every result.
+#[derive(Clone, Debug, Default)]
+pub struct Query {
+    word_set: Option<String>,
+    mode: Option<Mode>,
+    word_count: Option<u32>,
+    punct: Option<bool>,
+    numbers: Option<bool>,
+    caps: Option<bool>,
+    symbols: Option<bool>,
+    filter: Option<Option<String>>,
+    lazy: Option<bool>,
+    since: Option<u64>,
+    until: Option<u64>,
+    quit: Option<bool>,
+    tags: Vec<String>,
+    order: Order,
+    limit: Option<usize>,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn word_set(mut self, word_set: &str) -> Self {
+        self.word_set = Some(word_set.into());
+        self
+    }
+
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    pub fn word_count(mut self, word_count: u32) -> Self {
+        self.word_count = Some(word_count);
+        self
+    }
+
+    pub fn punct(mut self, punct: bool) -> Self {
+        self.punct = Some(punct);
+        self
+    }
+
+    pub fn numbers(mut self, numbers: bool) -> Self {
+        self.numbers = Some(numbers);
+        self
+    }
+
+    pub fn caps(mut self, caps: bool) -> Self {
+        self.caps = Some(caps);
+        self
+    }
+
+    pub fn symbols(mut self, symbols: bool) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    /// Only results with the given word filters, or with none if `None`.
+    pub fn filter(mut self, filter: Option<&str>) -> Self {
+        self.filter = Some(filter.map(Into::into));
+        self
+    }
+
+    pub fn lazy(mut self, lazy: bool) -> Self {
+        self.lazy = Some(lazy);
+        self
+    }
+
+    /// Only results within a range of timestamps, in seconds since the Unix epoch.
+    pub fn between(mut self, range: Range<u64>) -> Self {
+        self.since = Some(range.start);
+        self.until = Some(range.end);
+        self
+    }
+
+    /// Only results from a timestamp on, in seconds since the Unix epoch.
+    pub fn since(mut self, timestamp: u64) -> Self {
+        self.since = Some(timestamp);
+        self
+    }
+
+    pub fn quit(mut self, quit: bool) -> Self {
+        self.quit = Some(quit);
+        self
+    }
+
+    /// Only results with the tag, in addition to any tags already required.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Order {
+    #[default]
+    Oldest,
+    #[cfg(test)]
+    Newest,
+    /// Highest speed first, with the earliest of equal speeds first.
+    Fastest,
+}
+
+/// Copies the database to `path`, replacing any previous copy.
+fn backup_to(conn: &Connection, path: &Path) -> Result<(), String> {
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| e.to_string())?;
+    }
+    conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum DbError {
+    Sqlite(rusqlite::Error),
+    TooNew(usize),
+    Backup(PathBuf, String),
+    Migration(usize, rusqlite::Error),
+    Encode(String),
+    Decode(i64, String),
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sqlite(e) => write!(f, "{}", e),
+            Self::TooNew(version) => write!(
+                f,
+                "Database schema version {} is newer than the supported version {}; \
+                 update typre to use it",
+                version,
+                MIGRATIONS.len()
+            ),
+            Self::Backup(path, e) => {
+                write!(
+                    f,
+                    "Failed to back up to '{}' before migrating: {}",
+                    path.display(),
+                    e
+                )
+            }
+            Self::Migration(version, e) => {
+                write!(f, "Migration to schema version {} failed: {}", version, e)
+            }
+            Self::Encode(e) => write!(f, "Failed to encode result: {}", e),
+            Self::Decode(id, e) => write!(f, "Failed to decode result {}: {}", id, e),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Sqlite(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::result::History;
+
+    fn result(timestamp: u64, wpm: f32, tags: &[&str]) -> TestResult {
+        TestResult {
+            timestamp,
+            duration: 30,
+            word_set: "english_1k".into(),
+            mode: Mode::Words,
+            word_count: 50,
+            punct: false,
+            numbers: false,
+            caps: false,
+            symbols: false,
+            filter: None,
+            lazy: false,
+            wpm,
+            acc: 100.0,
+            cons: 100.0,
+            errors: 0,
+            quit: false,
+            tags: tags.iter().map(|&t| t.into()).collect(),
+            history: History {
+                wpm: vec![wpm as u16],
+                err: vec![0],
+            },
+        }
+    }
+
+    fn db() -> Db {
+        let db = Db::new(":memory:").unwrap();
+        db.save_result(&result(1, 50.0, &["home", "split"]))
+            .unwrap();
+        db.save_result(&result(2, 70.0, &["home"])).unwrap();
+        db.save_result(&result(3, 60.0, &["home", "split"]))
+            .unwrap();
+        db.save_result(&result(4, 80.0, &[])).unwrap();
+        db
+    }
+
+    fn timestamps(results: &[TestResult]) -> Vec<u64> {
+        results.iter().map(|r| r.timestamp).collect()
+    }
+
+    #[test]
+    fn migrates_to_latest_version() {
+        let db = Db::new(":memory:").unwrap();
+        let version: usize = db
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+
+    #[test]
+    fn migrates_version_2_with_backup() {
+        let dir = std::env::temp_dir().join(format!("typre-db-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("typre.db");
+        {
+            let mut conn = Connection::open(&path).unwrap();
+            for migration in &MIGRATIONS[..2] {
+                let tx = conn.transaction().unwrap();
+                migration(&tx).unwrap();
+                tx.commit().unwrap();
+            }
+            conn.pragma_update(None, "user_version", 2).unwrap();
+            conn.execute(
+                "INSERT INTO results (timestamp, duration, word_set, word_count, punct, numbers,
+                                      wpm, acc, cons, errors, quit, history)
+                 VALUES (1, 30, 'english_1k', 50, 0, 0, 60.0, 100.0, 100.0, 0, 0, x'0000')",
+                [],
+            )
+            .unwrap();
+        }
+
+        let db = Db::new(&path).unwrap();
+        let results = db.results(&Query::new().mode(Mode::Words)).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].tags.is_empty());
+        db.save_result(&result(2, 70.0, &["home"])).unwrap();
+        assert_eq!(db.results(&Query::new().tag("home")).unwrap().len(), 1);
+        assert!(dir.join("typre.db.v2.bak").exists());
+
+        drop(db);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn tags_must_all_match() {
+        let db = db();
+        let home = db.results(&Query::new().tag("home")).unwrap();
+        assert_eq!(timestamps(&home), [1, 2, 3]);
+        let both = db.results(&Query::new().tag("home").tag("split")).unwrap();
+        assert_eq!(timestamps(&both), [1, 3]);
+        assert_eq!(both[0].tags, ["home", "split"]);
+        let none = db.results(&Query::new().tag("split").tag("work")).unwrap();
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn order_and_limit() {
+        let db = db();
+        let all = db.results(&Query::new()).unwrap();
+        assert_eq!(timestamps(&all), [1, 2, 3, 4]);
+        let newest = db
+            .results(&Query::new().order(Order::Newest).limit(2))
+            .unwrap();
+        assert_eq!(timestamps(&newest), [4, 3]);
+        let fastest = db
+            .results(&Query::new().order(Order::Fastest).limit(3))
+            .unwrap();
+        assert_eq!(timestamps(&fastest), [4, 2, 3]);
+        let range = db.results(&Query::new().between(2..4)).unwrap();
+        assert_eq!(timestamps(&range), [2, 3]);
+    }
+
+    #[test]
+    fn personal_bests_keep_modifiers_apart() {
+        let db = db();
+        let mut drill = result(5, 120.0, &[]);
+        drill.filter = Some("only 'asdf'".into());
+        db.save_result(&drill).unwrap();
+        let mut caps = result(6, 110.0, &[]);
+        caps.caps = true;
+        db.save_result(&caps).unwrap();
+
+        let best = |r: &TestResult| db.personal_best(r).unwrap().map(|r| r.timestamp);
+        assert_eq!(best(&result(7, 0.0, &[])), Some(4));
+        assert_eq!(best(&drill), Some(5));
+        assert_eq!(best(&caps), Some(6));
+    }
+
+    #[test]
+    fn corrupt_history_is_an_error() {
+        let db = db();
+        db.conn
+            .execute("UPDATE results SET history = x'ff' WHERE timestamp = 2", [])
+            .unwrap();
+        let id: i64 = db
+            .conn
+            .query_row("SELECT id FROM results WHERE timestamp = 2", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        match db.results(&Query::new()) {
+            Err(DbError::Decode(bad, _)) => assert_eq!(bad, id),
+            other => panic!("expected a decode error, got {:?}", other.map(|r| r.len())),
+        }
+        // Other results can still be read around it.
+        assert_eq!(db.results(&Query::new().since(3)).unwrap().len(), 2);
+    }
+}
@@ -1,54 +1,86 @@
 use std::{
+    fmt,
     fs::File,
     io::{self, Write},
     path::Path,
-    time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::db::Db;
+use crate::db::{Db, DbError, Query};
 
-pub fn csv<P: AsRef<Path>>(db: &Db, to: P) -> io::Result<()> {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
-    let results = db.get_results_range(0..now).unwrap();
+pub fn csv<P: AsRef<Path>>(db: &Db, to: P) -> Result<(), DumpError> {
+    let results = db.results(&Query::new()).map_err(DumpError::Db)?;
 
     let header = [
         "timestamp",
         "duration",
         "word_set",
+        "mode",
         "word_count",
         "punct",
         "numbers",
+        "caps",
+        "symbols",
+        "filter",
+        "lazy",
         "wpm",
         "acc",
         "cons",
         "errors",
         "quit",
+        "tags",
     ]
     .join(",");
 
-    let mut file = File::create(to)?;
-    writeln!(file, "{}", header)?;
+    let mut file = File::create(to).map_err(DumpError::Write)?;
+    writeln!(file, "{}", header).map_err(DumpError::Write)?;
 
     for result in results {
         let row = [
             result.timestamp.to_string(),
             result.duration.to_string(),
             result.word_set,
+            result.mode.to_string(),
             result.word_count.to_string(),
             result.punct.to_string(),
             result.numbers.to_string(),
+            result.caps.to_string(),
+            result.symbols.to_string(),
+            quote(&result.filter.unwrap_or_default()),
+            result.lazy.to_string(),
             result.wpm.to_string(),
             result.acc.to_string(),
             result.cons.to_string(),
             result.errors.to_string(),
             result.quit.to_string(),
+            result.tags.join(";"),
         ]
         .join(",");
-        writeln!(file, "{}", row)?;
+        writeln!(file, "{}", row).map_err(DumpError::Write)?;
     }
 
     Ok(())
 }
+
+/// Quotes a field if it contains a separator or quote.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into()
+    }
+}
+
+#[derive(Debug)]
+pub enum DumpError {
+    Db(DbError),
+    Write(io::Error),
+}
+
+impl fmt::Display for DumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Db(e) => write!(f, "Failed to read results: {}", e),
+            Self::Write(e) => write!(f, "Failed to write CSV: {}", e),
+        }
+    }
+}
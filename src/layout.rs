@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+/// QWERTY keys by row, unshifted then shifted, which the other layouts are given relative to.
+const QWERTY: [&str; 8] = [
+    "1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+    "!@#$%^&*()_+",
+    "QWERTYUIOP{}|",
+    "ASDFGHJKL:\"",
+    "ZXCVBNM<>?",
+];
+
+const BUILTIN: &[(&str, [&str; 8])] = &[
+    (
+        "dvorak",
+        [
+            "1234567890[]",
+            "',.pyfgcrl/=\\",
+            "aoeuidhtns-",
+            ";qjkxbmwvz",
+            "!@#$%^&*(){}",
+            "\"<>PYFGCRL?+|",
+            "AOEUIDHTNS_",
+            ":QJKXBMWVZ",
+        ],
+    ),
+    (
+        "colemak",
+        [
+            "1234567890-=",
+            "qwfpgjluy;[]\\",
+            "arstdhneio'",
+            "zxcvbkm,./",
+            "!@#$%^&*()_+",
+            "QWFPGJLUY:{}|",
+            "ARSTDHNEIO\"",
+            "ZXCVBKM<>?",
+        ],
+    ),
+    (
+        "colemak-dh",
+        [
+            "1234567890-=",
+            "qwfpbjluy;[]\\",
+            "arstgmneio'",
+            "zxcdvkh,./",
+            "!@#$%^&*()_+",
+            "QWFPBJLUY:{}|",
+            "ARSTGMNEIO\"",
+            "ZXCDVKH<>?",
+        ],
+    ),
+    (
+        "workman",
+        [
+            "1234567890-=",
+            "qdrwbjfup;[]\\",
+            "ashtgyneoi'",
+            "zxmcvkl,./",
+            "!@#$%^&*()_+",
+            "QDRWBJFUP:{}|",
+            "ASHTGYNEOI\"",
+            "ZXMCVKL<>?",
+        ],
+    ),
+];
+
+/// A keyboard layout emulated on top of a QWERTY system layout.
+#[derive(Clone, Default, Debug)]
+pub struct Layout {
+    /// Characters typed by each QWERTY key, for keys that differ.
+    keys: HashMap<char, char>,
+}
+
+impl Layout {
+    /// Names of the built-in layouts.
+    pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+        BUILTIN.iter().map(|(name, _)| *name)
+    }
+
+    /// A built-in layout by name, with `qwerty` leaving keys as they are.
+    pub fn builtin(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("qwerty") {
+            return Some(Self::default());
+        }
+
+        let (_, rows) = BUILTIN
+            .iter()
+            .find(|(builtin, _)| builtin.eq_ignore_ascii_case(name))?;
+        let keys = QWERTY
+            .iter()
+            .zip(rows)
+            .flat_map(|(qwerty, row)| qwerty.chars().zip(row.chars()))
+            .filter(|(qwerty, c)| qwerty != c)
+            .collect();
+        Some(Self { keys })
+    }
+
+    /// Reads a `[layouts.<name>]` table, with an optional built-in `base` layout and a `keys`
+    /// table mapping QWERTY keys to the characters they type instead.
+    pub fn from_value(value: &toml::Value) -> Result<Self, String> {
+        let mut layout = match value.get("base") {
+            Some(base) => {
+                let base = base
+                    .as_str()
+                    .ok_or_else(|| "'base' must be a layout name".to_string())?;
+                Self::builtin(base).ok_or_else(|| format!("Unknown base layout '{}'", base))?
+            }
+            None => Self::default(),
+        };
+
+        if let Some(keys) = value.get("keys") {
+            let keys = keys
+                .as_table()
+                .ok_or_else(|| "'keys' must be a table of characters".to_string())?;
+            for (key, typed) in keys {
+                let error = || format!("Invalid key '{}': must map one character to another", key);
+                let key = single_char(key).ok_or_else(error)?;
+                let typed = typed.as_str().and_then(single_char).ok_or_else(error)?;
+                if key == typed {
+                    layout.keys.remove(&key);
+                } else {
+                    layout.keys.insert(key, typed);
+                }
+            }
+        }
+
+        Ok(layout)
+    }
+
+    /// The character typed by a QWERTY key.
+    pub fn map(&self, key: char) -> char {
+        self.keys.get(&key).copied().unwrap_or(key)
+    }
+}
+
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Settings for lazy matching, where characters that are hard to type on a US keyboard may be
+/// typed as a plain equivalent.
+#[derive(Clone, Debug)]
+pub struct Lazy {
+    /// Whether accented letters may be typed without their accents, e.g. `e` for `é`.
+    pub strip_accents: bool,
+    /// Alternatives accepted for a target grapheme, e.g. `ss` for `ß`.
+    ///
+    /// Equivalence is one-way: the alternative is accepted for the target, not the reverse.
+    pub equivalents: HashMap<String, Vec<String>>,
+}
+
+impl Default for Lazy {
+    fn default() -> Self {
+        const EQUIVALENTS: &[(&str, &str)] = &[
+            ("ß", "ss"),
+            ("ẞ", "SS"),
+            ("æ", "ae"),
+            ("Æ", "AE"),
+            ("œ", "oe"),
+            ("Œ", "OE"),
+            ("ø", "o"),
+            ("Ø", "O"),
+            ("ł", "l"),
+            ("Ł", "L"),
+            ("đ", "d"),
+            ("Đ", "D"),
+            ("ð", "d"),
+            ("Ð", "D"),
+            ("þ", "th"),
+            ("Þ", "Th"),
+            ("ı", "i"),
+            ("‘", "'"),
+            ("’", "'"),
+            ("‚", "'"),
+            ("‛", "'"),
+            ("“", "\""),
+            ("”", "\""),
+            ("„", "\""),
+            ("‟", "\""),
+            ("«", "\""),
+            ("»", "\""),
+            ("‹", "'"),
+            ("›", "'"),
+            ("—", "-"),
+            ("–", "-"),
+            ("‐", "-"),
+            ("−", "-"),
+            ("…", "..."),
+        ];
+
+        let mut equivalents: HashMap<String, Vec<String>> = HashMap::new();
+        for &(target, typed) in EQUIVALENTS {
+            equivalents
+                .entry(target.into())
+                .or_default()
+                .push(typed.into());
+        }
+
+        Self {
+            strip_accents: true,
+            equivalents,
+        }
+    }
+}
+
+impl Lazy {
+    /// Reads a `[lazy]` table, with `strip_accents` and an `equivalents` table mapping target
+    /// characters to an accepted alternative or a list of them. Entries in `equivalents` replace
+    /// the built-in ones for the same character.
+    pub fn from_value(value: &toml::Value) -> Result<Self, String> {
+        let mut lazy = Self::default();
+
+        if let Some(strip) = value.get("strip_accents") {
+            lazy.strip_accents = strip
+                .as_bool()
+                .ok_or_else(|| "'strip_accents' must be true or false".to_string())?;
+        }
+
+        if let Some(equivalents) = value.get("equivalents") {
+            let table = equivalents
+                .as_table()
+                .ok_or_else(|| "'equivalents' must be a table".to_string())?;
+            for (target, typed) in table {
+                let error = || format!("Invalid equivalents for '{}'", target);
+                let typed = match typed {
+                    toml::Value::String(typed) => vec![typed.nfc().collect()],
+                    toml::Value::Array(typed) => typed
+                        .iter()
+                        .map(|t| t.as_str().map(|t| t.nfc().collect()))
+                        .collect::<Option<_>>()
+                        .ok_or_else(error)?,
+                    _ => return Err(error()),
+                };
+                if target.is_empty() || typed.iter().any(String::is_empty) {
+                    return Err(error());
+                }
+                lazy.equivalents.insert(target.nfc().collect(), typed);
+            }
+        }
+
+        Ok(lazy)
+    }
+
+    /// The ways a target grapheme may be typed besides itself.
+    pub fn alternatives(&self, target: &str) -> Vec<String> {
+        let mut alternatives = self.equivalents.get(target).cloned().unwrap_or_default();
+        if self.strip_accents {
+            let stripped: String = target.nfd().filter(|&c| !is_combining_mark(c)).collect();
+            if !stripped.is_empty() && stripped != target {
+                alternatives.push(stripped);
+            }
+        }
+        alternatives
+    }
+}
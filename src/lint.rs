@@ -0,0 +1,176 @@
+use std::{collections::HashMap, fmt};
+
+use unicode_normalization::{is_nfc, is_nfd};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// A word as read from a word set file, before checking.
+#[derive(Debug)]
+pub struct Entry {
+    pub location: Location,
+    pub word: String,
+    pub weight: Option<f64>,
+}
+
+/// Where an entry is in its file.
+#[derive(Clone, Copy, Debug)]
+pub enum Location {
+    /// Line number, for line-based formats.
+    Line(usize),
+    /// Position in the word list, for JSON.
+    Index(usize),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Line(line) => write!(f, "line {}", line),
+            Self::Index(i) => write!(f, "word {}", i),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Issue {
+    /// Index of the offending entry.
+    pub entry: usize,
+    pub location: Location,
+    pub kind: IssueKind,
+}
+
+#[derive(Clone, Debug)]
+pub enum IssueKind {
+    Empty,
+    /// Repeats the entry at the given location.
+    Duplicate(Location),
+    Control(char),
+    Whitespace,
+    /// Renders with no width at all.
+    Invisible,
+    ZeroWidth(char),
+    AmbiguousWidth(char),
+    /// The set mixes composed (NFC) and decomposed (NFD) entries, counting the entries only in
+    /// each form. Reported on the first entry of the less common form.
+    MixedNormalization {
+        nfc: usize,
+        nfd: usize,
+    },
+}
+
+impl Issue {
+    /// Whether the entry can't be typed, so it is skipped when loading the set.
+    pub fn skips_entry(&self) -> bool {
+        matches!(
+            self.kind,
+            IssueKind::Empty | IssueKind::Control(_) | IssueKind::Whitespace | IssueKind::Invisible
+        )
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.location)?;
+        match &self.kind {
+            IssueKind::Empty => write!(f, "empty entry"),
+            IssueKind::Duplicate(first) => write!(f, "duplicate of {}", first),
+            IssueKind::Control(c) => write!(f, "control character {}", codepoint(*c)),
+            IssueKind::Whitespace => write!(f, "whitespace inside entry"),
+            IssueKind::Invisible => write!(f, "entry has no visible width"),
+            IssueKind::ZeroWidth(c) => write!(f, "zero-width character {}", codepoint(*c)),
+            IssueKind::AmbiguousWidth(c) => write!(
+                f,
+                "ambiguous-width character {} '{}' may render wide in some terminals",
+                codepoint(*c),
+                c
+            ),
+            IssueKind::MixedNormalization { nfc, nfd } => write!(
+                f,
+                "mixed normalization forms: {} composed (NFC) and {} decomposed (NFD) entries",
+                nfc, nfd
+            ),
+        }
+    }
+}
+
+fn codepoint(c: char) -> String {
+    format!("U+{:04X}", c as u32)
+}
+
+/// Zero-width format characters, which are invisible when rendered.
+fn is_zero_width_format(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}' | '\u{200B}'..='\u{200F}' | '\u{2060}'..='\u{2064}' | '\u{FEFF}'
+    )
+}
+
+/// Checks the entries of a word set, returning issues in entry order.
+pub fn check(entries: &[Entry]) -> Vec<Issue> {
+    let mut issues = Vec::new();
+    let mut seen: HashMap<&str, Location> = HashMap::new();
+    let mut nfc_only = Vec::new();
+    let mut nfd_only = Vec::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        let word = &*entry.word;
+        let mut issue = |kind| {
+            issues.push(Issue {
+                entry: i,
+                location: entry.location,
+                kind,
+            })
+        };
+
+        if word.is_empty() {
+            issue(IssueKind::Empty);
+            continue;
+        }
+
+        if let Some(c) = word.chars().find(|c| c.is_control()) {
+            issue(IssueKind::Control(c));
+        }
+        if word.chars().any(char::is_whitespace) {
+            issue(IssueKind::Whitespace);
+        }
+        if word.width() == 0 {
+            issue(IssueKind::Invisible);
+        } else if let Some(c) = word.chars().find(|&c| is_zero_width_format(c)) {
+            issue(IssueKind::ZeroWidth(c));
+        }
+        if let Some(c) = word.chars().find(|c| c.width() != c.width_cjk()) {
+            issue(IssueKind::AmbiguousWidth(c));
+        }
+
+        match seen.get(word) {
+            Some(&first) => issue(IssueKind::Duplicate(first)),
+            None => {
+                seen.insert(word, entry.location);
+            }
+        }
+
+        match (is_nfc(word), is_nfd(word)) {
+            (true, false) => nfc_only.push(i),
+            (false, true) => nfd_only.push(i),
+            _ => (),
+        }
+    }
+
+    if !nfc_only.is_empty() && !nfd_only.is_empty() {
+        let minority = if nfc_only.len() < nfd_only.len() {
+            nfc_only[0]
+        } else {
+            nfd_only[0]
+        };
+        let issue = Issue {
+            entry: minority,
+            location: entries[minority].location,
+            kind: IssueKind::MixedNormalization {
+                nfc: nfc_only.len(),
+                nfd: nfd_only.len(),
+            },
+        };
+        let at = issues.partition_point(|i| i.entry <= minority);
+        issues.insert(at, issue);
+    }
+
+    issues
+}
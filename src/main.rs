@@ -1,21 +1,42 @@
 #[allow(dead_code)]
 mod rand;
 
+mod caps;
+mod chart;
+mod compose;
 mod config;
+mod corpus;
 mod db;
 mod dump;
+mod layout;
+mod lazy;
+mod lint;
+mod markov;
+mod numbers;
+mod punct;
 mod result;
+mod stats;
+mod symbols;
 mod test;
 mod theme;
+mod trend;
 mod ui;
 mod words;
 
-use std::{path::PathBuf, process};
+use std::{
+    fs,
+    path::PathBuf,
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+use chart::ChartOptions;
 use config::Config;
-use db::Db;
+use corpus::BuildOptions;
+use db::{Db, Query};
 use theme::Theme;
-use words::WordSet;
+use trend::TrendOptions;
+use words::{Filter, Options, SetSource, WordSet};
 
 fn main() {
     let args = parse_args().unwrap_or_else(|e| {
@@ -23,20 +44,108 @@ fn main() {
         process::exit(1);
     });
 
-    let config_path = args
-        .config
-        .or_else(|| config::default_config_dir().map(|dir| dir.join("config.toml")));
-    let config = match config_path {
-        Some(path) if path.exists() => Config::load(&path).unwrap_or_else(|e| {
-            eprintln!("Could not load config '{}'...", path.display());
+    if args.init {
+        match config::init(args.force) {
+            Ok(written) => {
+                for path in written {
+                    println!("Wrote '{}'.", path.display());
+                }
+                process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("Could not write starter config...");
+                eprintln!("  {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // Without a config file, everything runs on the built-in defaults.
+    let config_path = args.config.or_else(|| {
+        config::default_config_dir()
+            .map(|dir| dir.join("config.toml"))
+            .filter(|path| path.exists())
+    });
+    let config = match &config_path {
+        Some(path) => Config::load(path),
+        None => Config::builtin(),
+    }
+    .unwrap_or_else(|e| {
+        match config_path {
+            Some(path) => eprintln!("Could not load config '{}'...", path.display()),
+            None => eprintln!("Could not load default config..."),
+        }
+        eprintln!("  {}", e);
+        process::exit(1);
+    });
+
+    for warning in &config.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+
+    if let Some(Command::BuildSet {
+        corpus,
+        out,
+        top,
+        keep_case,
+    }) = &args.command
+    {
+        let options = BuildOptions {
+            top: *top,
+            fold_case: !keep_case,
+            filter: args.filter,
+        };
+        match corpus::build_set(corpus, &config.sets_dirs[0], out, args.force, &options) {
+            Ok((path, count)) => {
+                println!("Wrote {} words to '{}'.", count, path.display());
+                process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("Could not build word set '{}'...", out);
+                eprintln!("  {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Some(name) = args.check_set {
+        let set = match config.sets.get(&name) {
+            Some(SetSource::File(path)) => WordSet::load(path),
+            Some(SetSource::Builtin(name)) => WordSet::builtin(name),
+            Some(_) => {
+                eprintln!("Word set '{}' is not a word set file.", name);
+                process::exit(1);
+            }
+            None => {
+                eprintln!("Word set '{}' is not available.", name);
+                process::exit(1);
+            }
+        };
+        let set = set.unwrap_or_else(|e| {
+            eprintln!("Could not load word set '{}'...", name);
             eprintln!("  {}", e);
             process::exit(1);
-        }),
-        _ => {
-            eprintln!("Configuration required, either in $XDG_CONFIG_HOME/typre/config.toml or via --config PATH.");
-            process::exit(1);
+        });
+        if set.issues.is_empty() {
+            println!("No issues in word set '{}'.", name);
+            process::exit(0);
         }
-    };
+        println!("Issues in word set '{}':", name);
+        for issue in &set.issues {
+            let skipped = if issue.skips_entry() {
+                " (skipped)"
+            } else {
+                ""
+            };
+            println!("  {}{}", issue, skipped);
+        }
+        process::exit(1);
+    }
+
+    if let Some(dir) = config.db_path.parent() {
+        // Failing here is reported by opening the database.
+        let _ = fs::create_dir_all(dir);
+    }
 
     let db = Db::new(&config.db_path).unwrap_or_else(|e| {
         eprintln!("Could not load database '{}'...", config.db_path.display());
@@ -45,16 +154,80 @@ fn main() {
     });
 
     if let Some(path) = args.csv {
-        dump::csv(&db, path).unwrap();
+        if let Err(e) = dump::csv(&db, &path) {
+            eprintln!("Could not dump database to '{}'...", path.display());
+            eprintln!("  {}", e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    if let Some(Command::Stats(query)) = &args.command {
+        let results = db.results(query).unwrap_or_else(|e| {
+            eprintln!("Could not read results from database...");
+            eprintln!("  {}", e);
+            process::exit(1);
+        });
+        stats::print_summary(&results);
+        process::exit(0);
+    }
+
+    if let Some(Command::Trend { query, options }) = &args.command {
+        let results = db.results(query).unwrap_or_else(|e| {
+            eprintln!("Could not read results from database...");
+            eprintln!("  {}", e);
+            process::exit(1);
+        });
+        trend::print_trend(&results, options);
+        process::exit(0);
+    }
+
+    if let Some(Command::Chart { query, options }) = &args.command {
+        let results = db.results(query).unwrap_or_else(|e| {
+            eprintln!("Could not read results from database...");
+            eprintln!("  {}", e);
+            process::exit(1);
+        });
+        let width = termion::terminal_size().map_or(80, |(width, _)| width as usize);
+        chart::print_chart(&results, options, width);
         process::exit(0);
     }
 
     if args.list_sets {
         println!("Available word sets");
-        println!(
-            "  {}",
-            config.sets.keys().cloned().collect::<Vec<_>>().join(", ")
-        );
+        let mut names: Vec<_> = config.sets.keys().collect();
+        names.sort();
+        for name in names {
+            match &config.sets[name] {
+                SetSource::File(path) => match WordSet::load_metadata(path) {
+                    Ok(meta) => print_set_info(name, &meta),
+                    Err(e) => println!("  {} (could not read header: {})", name, e),
+                },
+                SetSource::Builtin(_) => match WordSet::resolve(&config.sets, name) {
+                    Ok(set) => print_set_info(&format!("{} (built-in)", name), &set.meta),
+                    Err(e) => println!("  {} (could not load: {})", name, e),
+                },
+                SetSource::Composite(parts) => {
+                    let total: f64 = parts.iter().map(|(_, w)| w).sum();
+                    let parts: Vec<_> = parts
+                        .iter()
+                        .map(|(part, w)| format!("{} {:.0}%", part, 100.0 * w / total))
+                        .collect();
+                    println!("  {} (composite: {})", name, parts.join(", "));
+                }
+                SetSource::Markov {
+                    corpus,
+                    level,
+                    order,
+                } => println!(
+                    "  {} ({}-level order {} model of '{}')",
+                    name,
+                    level,
+                    order,
+                    corpus.display()
+                ),
+            }
+        }
     }
 
     if args.list_themes {
@@ -80,19 +253,24 @@ fn main() {
     }
 
     let set_name = args.set.unwrap();
-    let set_path = config.sets.get(&set_name).unwrap_or_else(|| {
-        eprintln!("Word set '{}' is not available.", set_name);
+    let set = WordSet::resolve(&config.sets, &set_name).unwrap_or_else(|e| {
+        eprintln!("Could not load word set '{}'...", set_name);
+        eprintln!("  {}", e);
         process::exit(1);
     });
-    let set = WordSet::load(&set_path).unwrap_or_else(|e| {
+
+    if !set.issues.is_empty() {
+        let skipped = set.issues.iter().filter(|i| i.skips_entry()).count();
         eprintln!(
-            "Could not load word set '{}' from path '{}'...",
+            "Warning: word set '{}' has {} issue{} ({} entr{} skipped); see --check-set {}.",
             set_name,
-            set_path.display()
+            set.issues.len(),
+            if set.issues.len() > 1 { "s" } else { "" },
+            skipped,
+            if skipped == 1 { "y" } else { "ies" },
+            set_name
         );
-        eprintln!("  {}", e);
-        process::exit(1);
-    });
+    }
 
     let mut theme = match args.theme {
         Some(name) => config.themes.get(&name).unwrap_or_else(|| {
@@ -106,33 +284,213 @@ fn main() {
         theme.bg.take();
     }
 
-    match test::run_test(&set, args.word_count, args.punct, args.numbers, theme)
-        .expect("UI crashed")
-    {
+    let punct = if args.punct {
+        true
+    } else if args.no_punct {
+        false
+    } else {
+        set.meta.punct.unwrap_or(false)
+    };
+
+    let direction = if args.rtl {
+        words::Direction::Rtl
+    } else if args.ltr {
+        words::Direction::Ltr
+    } else {
+        set.meta.direction
+    };
+
+    let layout = args.layout.as_ref().or(config.layout.as_ref()).map(|name| {
+        config.get_layout(name).unwrap_or_else(|| {
+            let mut names: Vec<&str> = layout::Layout::builtin_names().collect();
+            names.extend(config.layouts.keys().map(String::as_str));
+            names.sort_unstable();
+            eprintln!("Layout '{}' is not available.", name);
+            eprintln!("  Available layouts: qwerty, {}", names.join(", "));
+            process::exit(1);
+        })
+    });
+
+    let options = Options {
+        punct: punct.then(|| config.punct_rules(&set.meta)),
+        numbers: args.numbers.then(|| {
+            let mut numbers = config.numbers.clone();
+            if let Some(density) = args.number_density {
+                numbers.density = density;
+            }
+            numbers
+        }),
+        caps: args.caps.then(|| config.caps.clone()),
+        symbols: args.symbols.then(|| config.symbols.clone()),
+        filter: args.filter,
+        lazy: args.lazy.then(|| config.lazy.clone()),
+        compose: args.compose.then(|| config.compose.clone()),
+        layout,
+    };
+
+    if set.is_generated() && !options.filter.is_empty() {
+        eprintln!(
+            "Word filters cannot be used with generated set '{}'.",
+            set_name
+        );
+        process::exit(1);
+    }
+
+    if set.filter(&options.filter).is_empty() {
+        eprintln!("No words in set '{}' match the word filters.", set_name);
+        process::exit(1);
+    }
+
+    match test::run_test(&set, args.word_count, &options, theme, direction).expect("UI crashed") {
         Some(raw) => {
-            let result = result::process_raw(&set_name, &raw);
-            println!("{:#?}", result);
+            let mut result = result::process_raw(&set_name, &raw);
+            result.tags = args.tags;
+            print_result(&result);
+
+            // Quit tests are saved but never count as a personal best.
+            let best = if result.quit {
+                None
+            } else {
+                Some(db.personal_best(&result).unwrap_or_else(|e| {
+                    eprintln!("Warning: could not look up personal best: {}", e);
+                    None
+                }))
+            };
             db.save_result(&result).unwrap_or_else(|e| {
                 eprintln!("Could not save result to database...");
                 eprintln!("  {}", e);
             });
+
+            match best {
+                Some(Some(best)) if result.wpm > best.wpm => println!(
+                    "New personal best for {}: {:.1} wpm (+{:.1}).",
+                    result.category(),
+                    result.wpm,
+                    result.wpm - best.wpm
+                ),
+                Some(Some(best)) => println!(
+                    "Personal best for {}: {:.1} wpm ({:.1} to go).",
+                    result.category(),
+                    best.wpm,
+                    best.wpm - result.wpm
+                ),
+                Some(None) => println!("First personal best for {}.", result.category()),
+                None => (),
+            }
         }
         None => println!("No test started."),
     }
 }
 
+fn print_result(result: &result::TestResult) {
+    println!(
+        "{:.1} wpm, {:.1}% accuracy, {:.1}% consistency, {} error{} in {}s{}",
+        result.wpm,
+        result.acc,
+        result.cons,
+        result.errors,
+        if result.errors == 1 { "" } else { "s" },
+        result.duration,
+        if result.quit { " (quit)" } else { "" }
+    );
+}
+
+fn print_set_info(name: &str, meta: &words::Metadata) {
+    let mut details = Vec::new();
+    if let Some(language) = &meta.language {
+        details.push(language.clone());
+    }
+    if meta.direction != words::Direction::default() {
+        details.push(meta.direction.to_string());
+    }
+    if meta.ordered {
+        details.push("ordered".into());
+    }
+    if meta.punct == Some(true) {
+        details.push("punct".into());
+    }
+
+    print!("  {}", name);
+    if let Some(display) = &meta.name {
+        print!(" - {}", display);
+    }
+    if !details.is_empty() {
+        print!(" ({})", details.join(", "));
+    }
+    println!();
+    if let Some(description) = &meta.description {
+        println!("      {}", description);
+    }
+}
+
 const HELP: &str = "\
 typre
 
 USAGE:
   typre [OPTIONS] --set WORDSET
+  typre [OPTIONS] build-set CORPUS --out NAME
+  typre [OPTIONS] stats
+  typre [OPTIONS] trend
+  typre [OPTIONS] chart
+  typre --init [--force]
+
+COMMANDS:
+  build-set CORPUS   Build a frequency-ordered word set from a text file into the sets directory.
+    --out NAME         Set the name of the new word set.
+    --top NUMBER       Keep the most frequent words [default: 1000].
+    --keep-case        Count words case-sensitively instead of lowercasing them.
+    --force            Overwrite an existing word set.
+    The --min-len, --max-len, --only and --contains filters also apply.
+  stats              Summarize past results: totals, speed and accuracy, and a breakdown by
+                     word set.
+    --days NUMBER      Only include results from the last NUMBER days.
+    --since DATE       Only include results from DATE on, given as YYYY-MM-DD in UTC.
+    --until DATE       Only include results up to and including DATE.
+    The --set, --tag, --punct/--no-punct, --numbers, --caps, --symbols, --lazy and word
+    filter options select the results to include.
+  trend              Analyse progress: rolling averages, speed and accuracy trends, plateaus
+                     and when a target speed should be reached.
+    --window NUMBER    Set the number of tests in the rolling averages [default: 10].
+    --plateau NUMBER   Set the number of recent tests checked for a plateau, at least 3
+                       [default: 20].
+    --target WPM       Estimate when this speed will be reached.
+    The --days, --since and --until options and the options selecting results for stats
+    also apply.
+  chart              Chart speed or accuracy over time in the terminal.
+    --style STYLE      Set the chart style: line, braille or sparkline [default: line].
+    --by PERIOD        Chart each test, or weekly or monthly averages: tests, week or month
+                       [default: tests].
+    --metric METRIC    Set what to chart: wpm or acc [default: wpm].
+    --last NUMBER      Show the most recent tests or periods [default: 50].
+    The --days, --since and --until options and the options selecting results for stats
+    also apply.
 
 OPTIONS:
-  --set WORDSET      Select the word set to use.
+  --set WORDSET      Select the word set to use, or WORDSET:N for its N most frequent words.
   --count NUMBER     Set the number of words [default: 50].
-  --punct            Enable randomly added punctuation.
+  --punct, --no-punct
+                     Enable/disable randomly added punctuation [default: set by word set].
   --numbers          Enable randomly added numbers.
-  --config PATH      Set the configuration path.
+  --number-density FRACTION
+                     Set the fraction of words replaced by numbers [default: 0.0625].
+  --lazy             Accept plain equivalents of accented letters and typographic marks,
+                     e.g. e for é, ss for ß or ' for ’.
+  --compose          Enable compose sequences for characters not on the keyboard, e.g. ' then e
+                     for é, \" then s for ß or , then c for ç. Typing a mark twice types it
+                     as is, e.g. ' ' then a for 'a.
+  --layout LAYOUT    Emulate a keyboard layout over a QWERTY system layout: dvorak, colemak,
+                     colemak-dh, workman, or one defined in the configuration.
+  --rtl, --ltr       Lay out lines right-to-left/left-to-right [default: set by word set].
+  --caps             Enable random capitalization, including camelCase and snake_case.
+  --symbols          Enable randomly added programming symbols.
+  --min-len NUMBER   Only use words with at least this many characters.
+  --max-len NUMBER   Only use words with at most this many characters.
+  --only LETTERS     Only use words made up entirely of these letters.
+  --contains LETTERS Only use words containing at least one of these letters.
+  --tag TAG          Tag the saved result, e.g. for the keyboard used. Can be repeated.
+  --config PATH      Set the configuration path [default: $XDG_CONFIG_HOME/typre/config.toml].
+  --init             Write a commented starter config and the built-in word sets to the default
+                     directories. Use --force to replace an existing config.
   
   --theme THEME      Set the theme or override configuration [default: red & green].
   --bg, --no-bg      Enable/disable background color.
@@ -140,21 +498,56 @@ OPTIONS:
   --csv PATH         Dump database to CSV.
   --list-sets        List the available word sets.
   --list-themes      List the available themes.
+  --check-set WORDSET
+                     Check a word set file for problems such as duplicates or invisible characters.
   -h, --help         Display this message.
 ";
 
+enum Command {
+    BuildSet {
+        corpus: PathBuf,
+        out: String,
+        top: usize,
+        keep_case: bool,
+    },
+    Stats(Query),
+    Trend {
+        query: Query,
+        options: TrendOptions,
+    },
+    Chart {
+        query: Query,
+        options: ChartOptions,
+    },
+}
+
 struct Args {
+    command: Option<Command>,
     word_count: usize,
     set: Option<String>,
     config: Option<PathBuf>,
     punct: bool,
+    no_punct: bool,
     numbers: bool,
+    number_density: Option<f64>,
+    caps: bool,
+    symbols: bool,
+    lazy: bool,
+    compose: bool,
+    layout: Option<String>,
+    tags: Vec<String>,
+    rtl: bool,
+    ltr: bool,
+    filter: Filter,
     theme: Option<String>,
     bg: bool,
     no_bg: bool,
     csv: Option<PathBuf>,
     list_sets: bool,
     list_themes: bool,
+    check_set: Option<String>,
+    init: bool,
+    force: bool,
 }
 
 fn parse_args() -> Result<Args, pico_args::Error> {
@@ -165,11 +558,28 @@ fn parse_args() -> Result<Args, pico_args::Error> {
         process::exit(0);
     }
 
-    let args = Args {
+    let mut args = Args {
+        command: None,
         set: pargs.opt_value_from_str("--set")?,
         word_count: pargs.opt_value_from_str("--count")?.unwrap_or(50),
         punct: pargs.contains("--punct"),
+        no_punct: pargs.contains("--no-punct"),
         numbers: pargs.contains("--numbers"),
+        number_density: pargs.opt_value_from_fn("--number-density", numbers::parse_density)?,
+        caps: pargs.contains("--caps"),
+        symbols: pargs.contains("--symbols"),
+        lazy: pargs.contains("--lazy"),
+        compose: pargs.contains("--compose"),
+        layout: pargs.opt_value_from_str("--layout")?,
+        tags: pargs.values_from_str("--tag")?,
+        rtl: pargs.contains("--rtl"),
+        ltr: pargs.contains("--ltr"),
+        filter: Filter {
+            min_len: pargs.opt_value_from_str("--min-len")?,
+            max_len: pargs.opt_value_from_str("--max-len")?,
+            only: pargs.opt_value_from_str("--only")?,
+            contains: pargs.opt_value_from_str("--contains")?,
+        },
         config: pargs.opt_value_from_str("--config")?,
         theme: pargs.opt_value_from_str("--theme")?,
         bg: pargs.contains("--bg"),
@@ -177,6 +587,9 @@ fn parse_args() -> Result<Args, pico_args::Error> {
         csv: pargs.opt_value_from_str("--csv")?,
         list_sets: pargs.contains("--list-sets"),
         list_themes: pargs.contains("--list-themes"),
+        check_set: pargs.opt_value_from_str("--check-set")?,
+        init: pargs.contains("--init"),
+        force: pargs.contains("--force"),
     };
 
     if args.bg && args.no_bg {
@@ -184,6 +597,93 @@ fn parse_args() -> Result<Args, pico_args::Error> {
         process::exit(1);
     }
 
+    if args.punct && args.no_punct {
+        eprintln!("Error: --punct and --no-punct are mutually exclusive.");
+        process::exit(1);
+    }
+
+    if args.rtl && args.ltr {
+        eprintln!("Error: --rtl and --ltr are mutually exclusive.");
+        process::exit(1);
+    }
+
+    // Command options are parsed up front, as free arguments can only be read after all options.
+    let out: Option<String> = pargs.opt_value_from_str("--out")?;
+    let top: Option<usize> = pargs.opt_value_from_str("--top")?;
+    let keep_case = pargs.contains("--keep-case");
+    let days: Option<u64> = pargs.opt_value_from_str("--days")?;
+    let since: Option<i64> = pargs.opt_value_from_fn("--since", trend::parse_date)?;
+    let until: Option<i64> = pargs.opt_value_from_fn("--until", trend::parse_date)?;
+    let period = Period { days, since, until };
+    let window: Option<usize> = pargs.opt_value_from_str("--window")?;
+    let plateau: Option<usize> = pargs.opt_value_from_str("--plateau")?;
+    let target: Option<f32> = pargs.opt_value_from_str("--target")?;
+    let style: Option<chart::Style> = pargs.opt_value_from_str("--style")?;
+    let by: Option<chart::Period> = pargs.opt_value_from_str("--by")?;
+    let metric: Option<chart::Metric> = pargs.opt_value_from_str("--metric")?;
+    let last: Option<usize> = pargs.opt_value_from_str("--last")?;
+
+    let command: Option<String> = pargs.opt_free_from_str()?;
+    args.command = match command.as_deref() {
+        Some("build-set") => Some(Command::BuildSet {
+            corpus: pargs.free_from_str()?,
+            out: out.ok_or(pico_args::Error::MissingOption(pico_args::Keys::from(
+                "--out",
+            )))?,
+            top: top.unwrap_or(1000),
+            keep_case,
+        }),
+        Some("stats") => Some(Command::Stats(results_query(&args, &period))),
+        Some("trend") if plateau.is_some_and(|plateau| plateau < 3) => {
+            eprintln!("Error: --plateau must be at least 3.");
+            process::exit(1);
+        }
+        Some("trend") => Some(Command::Trend {
+            query: results_query(&args, &period),
+            options: TrendOptions {
+                window: window.unwrap_or(10),
+                plateau: plateau.unwrap_or(20),
+                target,
+            },
+        }),
+        Some("chart") => Some(Command::Chart {
+            query: results_query(&args, &period),
+            options: ChartOptions {
+                style: style.unwrap_or(chart::Style::Line),
+                period: by.unwrap_or(chart::Period::Test),
+                metric: metric.unwrap_or(chart::Metric::Wpm),
+                last: last.unwrap_or(50),
+            },
+        }),
+        Some(command) => {
+            eprintln!("Error: unknown command '{}'.", command);
+            process::exit(1);
+        }
+        None => {
+            if out.is_some() || top.is_some() || keep_case {
+                eprintln!("Error: --out, --top and --keep-case require build-set.");
+                process::exit(1);
+            }
+            if days.is_some() || since.is_some() || until.is_some() {
+                eprintln!("Error: --days, --since and --until require stats, trend or chart.");
+                process::exit(1);
+            }
+            if window.is_some() || plateau.is_some() || target.is_some() {
+                eprintln!("Error: --window, --plateau and --target require trend.");
+                process::exit(1);
+            }
+            if style.is_some() || by.is_some() || metric.is_some() || last.is_some() {
+                eprintln!("Error: --style, --by, --metric and --last require chart.");
+                process::exit(1);
+            }
+            if args.force && !args.init {
+                eprintln!("Error: --force requires build-set or --init.");
+                process::exit(1);
+            }
+            None
+        }
+    };
+
     let remaining = pargs.finish();
     if !remaining.is_empty() {
         eprintln!(
@@ -200,3 +700,63 @@ fn parse_args() -> Result<Args, pico_args::Error> {
 
     Ok(args)
 }
+
+/// The time span of results selected by --days, --since and --until, with dates given as days
+/// since the Unix epoch.
+struct Period {
+    days: Option<u64>,
+    since: Option<i64>,
+    until: Option<i64>,
+}
+
+/// The results selected by the options shared by commands over past results.
+fn results_query(args: &Args, period: &Period) -> Query {
+    let mut query = Query::new();
+    if let Some(set) = &args.set {
+        query = query.word_set(set);
+    }
+    if args.punct || args.no_punct {
+        query = query.punct(args.punct);
+    }
+    if args.numbers {
+        query = query.numbers(true);
+    }
+    if args.caps {
+        query = query.caps(true);
+    }
+    if args.symbols {
+        query = query.symbols(true);
+    }
+    if !args.filter.is_empty() {
+        query = query.filter(Some(&args.filter.to_string()));
+    }
+    if args.lazy {
+        query = query.lazy(true);
+    }
+    for tag in &args.tags {
+        query = query.tag(tag);
+    }
+    const DAY: u64 = 24 * 60 * 60;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let day_start = |day: i64| (day.max(0) as u64).saturating_mul(DAY);
+    let since = [
+        period
+            .days
+            .map(|days| now.saturating_sub(days.saturating_mul(DAY))),
+        period.since.map(day_start),
+    ]
+    .into_iter()
+    .flatten()
+    .max();
+    // Up to the end of the day.
+    let until = period.until.map(|day| day_start(day.saturating_add(1)));
+    query = match (since, until) {
+        (since, Some(until)) => query.between(since.unwrap_or(0)..until),
+        (Some(since), None) => query.since(since),
+        (None, None) => query,
+    };
+    query
+}
@@ -0,0 +1,239 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::rand;
+
+/// Unit of text the model is trained on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Level {
+    /// Whitespace-separated words, keeping their punctuation and case.
+    Word,
+    /// Single characters, including spaces.
+    Char,
+}
+
+impl Level {
+    /// A reasonable n-gram order for the level.
+    pub fn default_order(self) -> usize {
+        match self {
+            Self::Word => 2,
+            Self::Char => 4,
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "word" => Ok(Self::Word),
+            "char" => Ok(Self::Char),
+            _ => Err(format!("Invalid level '{}': must be 'word' or 'char'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Word => write!(f, "word"),
+            Self::Char => write!(f, "char"),
+        }
+    }
+}
+
+/// Longest word generated at the character level, in characters.
+const MAX_WORD_LEN: usize = 24;
+
+/// An n-gram model producing pseudo-text with the transitions of a training corpus.
+#[derive(Clone, Debug)]
+pub struct Model {
+    level: Level,
+    /// Distinct tokens, indexed by the states below.
+    vocab: Vec<String>,
+    /// Maps the last `order` tokens to the possible next tokens and their counts.
+    transitions: HashMap<Vec<u32>, Vec<(u32, u32)>>,
+    /// States beginning a sentence, used to start and restart generation.
+    starts: Vec<Vec<u32>>,
+}
+
+impl Model {
+    pub fn train(text: &str, level: Level, order: usize) -> Self {
+        let order = order.max(1);
+        let tokens: Vec<&str> = match level {
+            Level::Word => text.split_whitespace().collect(),
+            Level::Char => {
+                // Collapse runs of whitespace so line breaks don't leak into generated words.
+                let mut tokens = Vec::new();
+                let mut last_space = true;
+                for (i, c) in text.char_indices() {
+                    if c.is_whitespace() {
+                        if !last_space {
+                            tokens.push(" ");
+                        }
+                        last_space = true;
+                    } else {
+                        tokens.push(&text[i..i + c.len_utf8()]);
+                        last_space = false;
+                    }
+                }
+                tokens
+            }
+        };
+
+        let mut vocab = Vec::new();
+        let mut ids = HashMap::new();
+        let tokens: Vec<u32> = tokens
+            .into_iter()
+            .map(|token| {
+                *ids.entry(token).or_insert_with(|| {
+                    vocab.push(token.to_string());
+                    vocab.len() as u32 - 1
+                })
+            })
+            .collect();
+
+        let space = ids.get(" ").copied();
+        let ends_sentence = |token: u32| vocab[token as usize].ends_with(['.', '?', '!']);
+
+        let mut counts: HashMap<Vec<u32>, HashMap<u32, u32>> = HashMap::new();
+        let mut starts = Vec::new();
+        let mut word_starts = Vec::new();
+        for (i, window) in tokens.windows(order + 1).enumerate() {
+            let (state, next) = window.split_at(order);
+            *counts
+                .entry(state.to_vec())
+                .or_default()
+                .entry(next[0])
+                .or_default() += 1;
+
+            let before = &tokens[..i];
+            let (word_start, sentence_start) = match level {
+                Level::Word => (true, before.last().is_none_or(|&t| ends_sentence(t))),
+                Level::Char => match before {
+                    [] => (true, true),
+                    [.., prev, last] if Some(*last) == space => (true, ends_sentence(*prev)),
+                    [last] => (Some(*last) == space, false),
+                    _ => (false, false),
+                },
+            };
+            if sentence_start {
+                starts.push(state.to_vec());
+            } else if word_start && Some(state[0]) != space {
+                word_starts.push(state.to_vec());
+            }
+        }
+
+        // Without any sentence ends in the corpus, any word is a fine place to start.
+        if starts.is_empty() {
+            starts = word_starts;
+        }
+
+        let transitions = counts
+            .into_iter()
+            .map(|(state, next)| (state, next.into_iter().collect()))
+            .collect();
+
+        Self {
+            level,
+            vocab,
+            transitions,
+            starts,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    /// Generates `amount` words by walking the model, restarting at a sentence start whenever
+    /// the walk reaches a state without successors.
+    ///
+    /// At the character level, words are cut off at `MAX_WORD_LEN` characters and the walk
+    /// restarts, so corpora without spaces (e.g. Chinese) still produce words. Fewer words are
+    /// returned if the walk keeps producing nothing.
+    pub fn generate(&self, amount: usize) -> Vec<String> {
+        let mut words = Vec::with_capacity(amount);
+        if self.is_empty() {
+            return words;
+        }
+
+        // Every step adds at least one character, so this allows for plenty of spaces.
+        let max_steps = (amount + 1).saturating_mul(4 * MAX_WORD_LEN);
+        let mut current = String::new();
+        let mut state: Vec<u32> = Vec::new();
+        for _ in 0..max_steps {
+            if words.len() >= amount {
+                break;
+            }
+            if current.chars().count() >= MAX_WORD_LEN {
+                words.push(std::mem::take(&mut current));
+                state.clear();
+            }
+
+            let next = match self.transitions.get(&state).and_then(|next| pick(next)) {
+                Some(next) => {
+                    state.remove(0);
+                    state.push(next);
+                    vec![next]
+                }
+                None => {
+                    state = self.starts[rand::usize(..self.starts.len())].clone();
+                    if self.level == Level::Char && !current.is_empty() {
+                        words.push(std::mem::take(&mut current));
+                    }
+                    state.clone()
+                }
+            };
+
+            for token in next {
+                let token = &self.vocab[token as usize];
+                match self.level {
+                    Level::Word => words.push(token.clone()),
+                    Level::Char if token == " " => {
+                        if !current.is_empty() {
+                            words.push(std::mem::take(&mut current));
+                        }
+                    }
+                    Level::Char => current.push_str(token),
+                }
+            }
+        }
+
+        words.truncate(amount);
+        words
+    }
+}
+
+/// Picks a token with probability proportional to its count.
+fn pick(next: &[(u32, u32)]) -> Option<u32> {
+    let total: usize = next.iter().map(|&(_, count)| count as usize).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut r = rand::usize(..total);
+    for &(token, count) in next {
+        if r < count as usize {
+            return Some(token);
+        }
+        r -= count as usize;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_without_spaces_still_makes_words() {
+        for text in ["ababababababab", "我们今天去公园散步然后回家吃饭。"] {
+            let model = Model::train(text, Level::Char, 4);
+            let words = model.generate(10);
+            assert_eq!(words.len(), 10);
+            assert!(words
+                .iter()
+                .all(|word| word.chars().count() <= MAX_WORD_LEN));
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::str::FromStr;
+
+use crate::{
+    config::{parse_fraction, parse_weights},
+    rand,
+};
+
+/// Settings for replacing words with numbers.
+#[derive(Clone, Debug)]
+pub struct Numbers {
+    /// Fraction of words replaced by numbers.
+    pub density: f64,
+    /// Relative weights of each kind of number.
+    pub kinds: Vec<(Kind, f64)>,
+}
+
+impl Default for Numbers {
+    fn default() -> Self {
+        Self {
+            density: 1.0 / 16.0,
+            kinds: vec![
+                (Kind::Integer, 4.0),
+                (Kind::Year, 2.0),
+                (Kind::Decimal, 1.0),
+                (Kind::Percent, 1.0),
+                (Kind::Time, 1.0),
+                (Kind::Date, 1.0),
+                (Kind::Phone, 0.5),
+                (Kind::Version, 0.5),
+            ],
+        }
+    }
+}
+
+impl Numbers {
+    /// Reads a `[numbers]` table, with `density` and a `kinds` table of weights. Kinds missing
+    /// from `kinds` are not generated.
+    pub fn from_value(value: &toml::Value) -> Result<Self, String> {
+        let mut numbers = Self::default();
+
+        if let Some(density) = value.get("density") {
+            numbers.density = parse_fraction(density, "density")?;
+        }
+
+        if let Some(kinds) = value.get("kinds") {
+            numbers.kinds = parse_weights(kinds, "kinds", "number kind")?
+                .into_iter()
+                .map(|(kind, weight)| Ok((kind.parse()?, weight)))
+                .collect::<Result<_, String>>()?;
+        }
+
+        Ok(numbers)
+    }
+
+    /// Replaces a `density` fraction of the words with numbers.
+    pub fn apply(&self, words: &mut [String]) {
+        let amount = (words.len() as f64 * self.density).round() as usize;
+        let indices = rand::choose_multiple(0..words.len(), amount);
+        for i in indices {
+            if let Some(kind) = self.choose_kind() {
+                words[i] = kind.generate();
+            }
+        }
+    }
+
+    fn choose_kind(&self) -> Option<Kind> {
+        rand::choose_weighted(&self.kinds).copied()
+    }
+}
+
+/// Parses a density given on the command line.
+pub fn parse_density(s: &str) -> Result<f64, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|d| (0.0..=1.0).contains(d))
+        .ok_or_else(|| format!("Invalid density '{}': must be a number between 0 and 1", s))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+    /// Integers of one to six digits, e.g. `7` or `40213`.
+    Integer,
+    /// e.g. `1987`.
+    Year,
+    /// e.g. `3.14`.
+    Decimal,
+    /// e.g. `45%` or `2.5%`.
+    Percent,
+    /// e.g. `09:30`.
+    Time,
+    /// e.g. `2021-03-14` or `14/03/2021`.
+    Date,
+    /// e.g. `555-123-4567`.
+    Phone,
+    /// e.g. `1.2.3`.
+    Version,
+}
+
+impl Kind {
+    pub fn generate(self) -> String {
+        match self {
+            Self::Integer => {
+                // Shorter numbers are more common.
+                let digits = 1 + (rand::f64() * rand::f64() * 6.0) as usize;
+                digit_string(digits)
+            }
+            Self::Year => rand::usize(1900..2030).to_string(),
+            Self::Decimal => format!(
+                "{}.{}",
+                rand::usize(..1000),
+                digit_string(rand::usize(1..=2))
+            ),
+            Self::Percent => {
+                if rand::usize(..4) == 0 {
+                    format!("{}.{}%", rand::usize(..100), rand::usize(1..10))
+                } else {
+                    format!("{}%", rand::usize(..=100))
+                }
+            }
+            Self::Time => format!("{:02}:{:02}", rand::usize(..24), rand::usize(..60)),
+            Self::Date => {
+                let (y, m, d) = (
+                    rand::usize(1950..2030),
+                    rand::usize(1..=12),
+                    rand::usize(1..=28),
+                );
+                if rand::usize(..2) == 0 {
+                    format!("{}-{:02}-{:02}", y, m, d)
+                } else {
+                    format!("{:02}/{:02}/{}", d, m, y)
+                }
+            }
+            Self::Phone => format!(
+                "{}-{}-{}",
+                digit_string(3),
+                digit_string(3),
+                digit_string(4)
+            ),
+            Self::Version => format!(
+                "{}.{}.{}",
+                rand::usize(..10),
+                rand::usize(..20),
+                rand::usize(..30)
+            ),
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match &*s.to_lowercase() {
+            "integer" => Self::Integer,
+            "year" => Self::Year,
+            "decimal" => Self::Decimal,
+            "percent" => Self::Percent,
+            "time" => Self::Time,
+            "date" => Self::Date,
+            "phone" => Self::Phone,
+            "version" => Self::Version,
+            _ => return Err(format!("Unknown number kind '{}'", s)),
+        })
+    }
+}
+
+/// A number with exactly `digits` digits and no leading zero.
+fn digit_string(digits: usize) -> String {
+    let mut s = rand::usize(1..10).to_string();
+    for _ in 1..digits {
+        s.push(char::from(b'0' + rand::u8(..10)));
+    }
+    s
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+
+use crate::{config, rand};
+
+/// Rules for randomly punctuating a list of words.
+///
+/// Each word gets at most one kind of punctuation, chosen with the given probabilities.
+#[derive(Clone, Debug)]
+pub struct Rules {
+    /// Chance of ending a sentence after a word.
+    pub terminal: f64,
+    /// Chance of a pause after a word.
+    pub pause: f64,
+    /// Chance of wrapping a word in delimiters.
+    pub delim: f64,
+    /// Chance of a standalone separator after a word.
+    pub sep: f64,
+    /// Chance of joining a word to the next one.
+    pub join: f64,
+    pub terminals: Vec<String>,
+    pub pauses: Vec<String>,
+    pub delims: Vec<(String, String)>,
+    pub seps: Vec<String>,
+    pub joins: Vec<String>,
+    /// Marks opening a sentence ended by the given terminal, e.g. `¿` for `?` in Spanish.
+    pub openers: HashMap<String, String>,
+    /// Marks set apart from their word by a space, e.g. `?` and `»` in French.
+    pub spaced: Vec<String>,
+    /// Whether to capitalize the first word of each sentence.
+    pub capitalize: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            terminal: 0.1,
+            pause: 0.1,
+            delim: 0.05,
+            sep: 0.025,
+            join: 0.025,
+            terminals: strings(&[".", "?", "!"]),
+            pauses: strings(&[",", ";", ":"]),
+            delims: pairs(&[
+                ("\"", "\""),
+                ("'", "'"),
+                ("(", ")"),
+                ("[", "]"),
+                ("{", "}"),
+                ("<", ">"),
+            ]),
+            seps: strings(&["-", "..."]),
+            joins: strings(&["-", "/"]),
+            openers: HashMap::new(),
+            spaced: Vec::new(),
+            capitalize: true,
+        }
+    }
+}
+
+impl Rules {
+    /// Built-in rules for a language tag, falling back to English-like defaults.
+    pub fn for_language(language: Option<&str>) -> Self {
+        let primary = language
+            .and_then(|tag| tag.split(['-', '_']).next())
+            .map(str::to_lowercase);
+        match primary.as_deref() {
+            Some("es") => Self {
+                delims: pairs(&[("«", "»"), ("\"", "\""), ("(", ")")]),
+                openers: [("?", "¿"), ("!", "¡")]
+                    .iter()
+                    .map(|&(k, v)| (k.into(), v.into()))
+                    .collect(),
+                ..Self::default()
+            },
+            Some("fr") => Self {
+                delims: pairs(&[("«", "»"), ("(", ")")]),
+                spaced: strings(&[";", ":", "!", "?", "«", "»"]),
+                ..Self::default()
+            },
+            Some("de") => Self {
+                delims: pairs(&[("„", "“"), ("‚", "‘"), ("(", ")")]),
+                ..Self::default()
+            },
+            _ => Self::default(),
+        }
+    }
+
+    /// Applies the settings given in a `[punctuation]` table, keeping the rest.
+    pub fn merge(&mut self, overrides: &Overrides) {
+        let Overrides(value) = overrides;
+        if let Some(p) = get_probability(value, "terminal") {
+            self.terminal = p;
+        }
+        if let Some(p) = get_probability(value, "pause") {
+            self.pause = p;
+        }
+        if let Some(p) = get_probability(value, "delim") {
+            self.delim = p;
+        }
+        if let Some(p) = get_probability(value, "sep") {
+            self.sep = p;
+        }
+        if let Some(p) = get_probability(value, "join") {
+            self.join = p;
+        }
+        if let Some(marks) = get_strings(value, "terminals") {
+            self.terminals = marks;
+        }
+        if let Some(marks) = get_strings(value, "pauses") {
+            self.pauses = marks;
+        }
+        if let Some(delims) = get_pairs(value, "delims") {
+            self.delims = delims;
+        }
+        if let Some(marks) = get_strings(value, "seps") {
+            self.seps = marks;
+        }
+        if let Some(marks) = get_strings(value, "joins") {
+            self.joins = marks;
+        }
+        if let Some(openers) = value.get("openers").and_then(|v| v.as_table()) {
+            self.openers = openers
+                .iter()
+                .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.to_string())))
+                .collect();
+        }
+        if let Some(marks) = get_strings(value, "spaced") {
+            self.spaced = marks;
+        }
+        if let Some(capitalize) = value.get("capitalize").and_then(|v| v.as_bool()) {
+            self.capitalize = capitalize;
+        }
+    }
+
+    /// Punctuates words taken from `words` until there are `count`, counting separators and
+    /// spaced marks as words. Fewer are returned only if `words` runs out.
+    pub fn apply(&self, words: impl Iterator<Item = String>, count: usize) -> Vec<String> {
+        let mut out: Vec<String> = Vec::with_capacity(count);
+        let mut sentence_start = 0;
+        let mut capitalize = self.capitalize;
+        let mut joined: Option<String> = None;
+
+        let mut words = words.peekable();
+        while out.len() < count {
+            let mut word = match (joined.take(), words.next()) {
+                (Some(prefix), Some(word)) => prefix + &word,
+                (None, Some(word)) => word,
+                (_, None) => break,
+            };
+            if capitalize {
+                capitalize_first(&mut word);
+                capitalize = false;
+            }
+
+            // Marks are left out near the end if their standalone words wouldn't fit.
+            let room = count - out.len();
+            let spaced = |mark: &str| usize::from(self.is_spaced(mark));
+            let mut before = None;
+            let mut after = None;
+            let mut ended = false;
+            let mut r = rand::f64();
+            if let Some(mark) = roll(&mut r, self.terminal, &self.terminals).filter(|mark| {
+                let opener = self.openers.get(*mark).map_or(0, |opener| spaced(opener));
+                opener + spaced(mark) < room
+            }) {
+                if let Some(opener) = self.openers.get(mark) {
+                    if sentence_start < out.len() {
+                        self.push_before(&mut out, sentence_start, opener);
+                    } else {
+                        before = Some(opener.as_str());
+                    }
+                }
+                after = Some(mark.as_str());
+                capitalize = self.capitalize;
+                ended = true;
+            } else if let Some(mark) =
+                roll(&mut r, self.pause, &self.pauses).filter(|mark| spaced(mark) < room)
+            {
+                after = Some(mark.as_str());
+            } else if let Some((open, close)) = roll(&mut r, self.delim, &self.delims)
+                .filter(|(open, close)| spaced(open) + spaced(close) < room)
+            {
+                before = Some(open.as_str());
+                after = Some(close.as_str());
+            } else if let Some(sep) = roll(&mut r, self.sep, &self.seps).filter(|_| room >= 2) {
+                out.push(word);
+                out.push(sep.clone());
+                continue;
+            } else if let Some(join) = roll(&mut r, self.join, &self.joins) {
+                if words.peek().is_some() {
+                    joined = Some(word + join);
+                    continue;
+                }
+            }
+
+            if let Some(mark) = before {
+                if self.is_spaced(mark) {
+                    out.push(mark.into());
+                } else {
+                    word.insert_str(0, mark);
+                }
+            }
+            let spaced_after = after.filter(|mark| self.is_spaced(mark));
+            if let (Some(mark), None) = (after, spaced_after) {
+                word.push_str(mark);
+            }
+            out.push(word);
+            if let Some(mark) = spaced_after {
+                out.push(mark.into());
+            }
+
+            if ended {
+                sentence_start = out.len();
+            }
+        }
+
+        out
+    }
+
+    fn is_spaced(&self, mark: &str) -> bool {
+        self.spaced.iter().any(|m| m == mark)
+    }
+
+    /// Adds an opening mark before the word at `i`.
+    fn push_before(&self, out: &mut Vec<String>, i: usize, mark: &str) {
+        if self.is_spaced(mark) {
+            out.insert(i, mark.into());
+        } else {
+            out[i].insert_str(0, mark);
+        }
+    }
+}
+
+/// Punctuation settings from a config or word set header, applied over built-in rules.
+#[derive(Clone, Debug)]
+pub struct Overrides(toml::Value);
+
+impl Overrides {
+    /// Validates a `[punctuation]` table.
+    pub fn from_value(value: &toml::Value) -> Result<Self, String> {
+        let table = value
+            .as_table()
+            .ok_or_else(|| "punctuation rules must be a table".to_string())?;
+
+        for (key, v) in table {
+            let valid = match &**key {
+                "terminal" | "pause" | "delim" | "sep" | "join" => {
+                    get_probability(value, key).is_some()
+                }
+                "terminals" | "pauses" | "seps" | "joins" | "spaced" => {
+                    get_strings(value, key).is_some()
+                }
+                "delims" => get_pairs(value, key).is_some(),
+                "openers" => v.as_table().is_some_and(|t| t.values().all(|v| v.is_str())),
+                "capitalize" => v.is_bool(),
+                _ => return Err(format!("unknown punctuation setting '{}'", key)),
+            };
+            if !valid {
+                return Err(format!("invalid value for punctuation setting '{}'", key));
+            }
+        }
+
+        Ok(Self(value.clone()))
+    }
+}
+
+/// Picks one of `choices` if `r` falls under probability `p`, otherwise moves `r` past it.
+fn roll<'a, T>(r: &mut f64, p: f64, choices: &'a [T]) -> Option<&'a T> {
+    if *r >= p {
+        *r -= p;
+        None
+    } else if choices.is_empty() {
+        // The roll landed on a kind without marks, so nothing else applies either.
+        *r = f64::INFINITY;
+        None
+    } else {
+        Some(&choices[rand::usize(..choices.len())])
+    }
+}
+
+/// Uppercases the first letter of the word, skipping leading marks.
+fn capitalize_first(word: &mut String) {
+    if let Some((i, c)) = word.char_indices().find(|(_, c)| c.is_alphabetic()) {
+        if c.is_lowercase() {
+            let upper: String = c.to_uppercase().collect();
+            word.replace_range(i..i + c.len_utf8(), &upper);
+        }
+    }
+}
+
+fn get_probability(value: &toml::Value, key: &str) -> Option<f64> {
+    value.get(key).and_then(config::as_fraction)
+}
+
+fn get_strings(value: &toml::Value, key: &str) -> Option<Vec<String>> {
+    value
+        .get(key)?
+        .as_array()?
+        .iter()
+        .map(|v| v.as_str().map(String::from))
+        .collect()
+}
+
+fn get_pairs(value: &toml::Value, key: &str) -> Option<Vec<(String, String)>> {
+    value
+        .get(key)?
+        .as_array()?
+        .iter()
+        .map(|v| match v.as_array()?.as_slice() {
+            [open, close] => Some((open.as_str()?.into(), close.as_str()?.into())),
+            _ => None,
+        })
+        .collect()
+}
+
+fn strings(marks: &[&str]) -> Vec<String> {
+    marks.iter().map(|&m| m.into()).collect()
+}
+
+fn pairs(marks: &[(&str, &str)]) -> Vec<(String, String)> {
+    marks.iter().map(|&(l, r)| (l.into(), r.into())).collect()
+}
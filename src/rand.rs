@@ -224,6 +224,14 @@ impl Rng {
         reservoir
     }
 
+    /// Shuffles a slice in place.
+    #[inline]
+    pub fn shuffle<T>(&self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            slice.swap(i, self.usize(..=i));
+        }
+    }
+
     /// Generates a random `f64` in range `0..1`.
     pub fn f64(&self) -> f64 {
         let b = 64;
@@ -297,11 +305,45 @@ pub fn choose_multiple<T: Iterator + Sized>(iter: T, amount: usize) -> Vec<T::It
     RNG.with(|rng| rng.choose_multiple(iter, amount))
 }
 
+/// Shuffles a slice in place.
+#[inline]
+pub fn shuffle<T>(slice: &mut [T]) {
+    RNG.with(|rng| rng.shuffle(slice))
+}
+
 /// Generates a random `f64` in range `0..1`.
 pub fn f64() -> f64 {
     RNG.with(|rng| rng.f64())
 }
 
+/// Chooses an item with probability proportional to its weight, or `None` if all weights are
+/// zero.
+pub fn choose_weighted<T>(items: &[(T, f64)]) -> Option<&T> {
+    let cumulative: Vec<f64> = items
+        .iter()
+        .scan(0.0, |total, (_, weight)| {
+            *total += weight;
+            Some(*total)
+        })
+        .collect();
+    choose_cumulative(&cumulative).map(|i| &items[i].0)
+}
+
+/// Chooses an index with probability proportional to its weight, given the running totals of
+/// the weights, or `None` if all weights are zero.
+pub fn choose_cumulative(cumulative: &[f64]) -> Option<usize> {
+    let total = *cumulative.last()?;
+    if total <= 0.0 {
+        return None;
+    }
+    let r = f64() * total;
+    match cumulative.partition_point(|&w| w <= r) {
+        i if i < cumulative.len() => Some(i),
+        // Rounding put `r` at the total, so take the last item with a weight.
+        _ => Some(cumulative.partition_point(|&w| w < total)),
+    }
+}
+
 macro_rules! integer {
     ($t:tt, $doc:tt) => {
         #[doc = $doc]
@@ -1,50 +1,188 @@
-use crate::test::TestRawResult;
+use std::{fmt, str::FromStr};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::test::{Diff, StepKind, TestRawResult};
+
+/// Characters per word when converting to words per minute.
+const WORD_LEN: f32 = 5.0;
 
 pub fn process_raw(word_set: &str, raw: &TestRawResult) -> TestResult {
     let duration = raw.duration.as_secs() as u32;
+    let minutes = raw.duration.as_secs_f32() / 60.0;
+    let first = raw.steps.first().map(|step| step.instant);
 
-    let wpm = 0.0;
-    let acc = 0.0;
-    let cons = 0.0;
-    let errors = 0;
-
-    let history = History {
+    // Each completed word counts with the space that completed it.
+    let mut chars = 0;
+    let mut correct = 0;
+    let mut errors = 0;
+    let mut word_start = None;
+    let mut word_speeds = Vec::new();
+    let mut history = History {
         wpm: Vec::new(),
         err: Vec::new(),
     };
+    for step in &raw.steps {
+        let second = first.map_or(0, |first| (step.instant - first).as_secs() as usize);
+        if history.wpm.len() <= second {
+            history.wpm.resize(second + 1, 0);
+            history.err.resize(second + 1, 0);
+        }
+
+        match &step.kind {
+            StepKind::Input(Diff::Correct(_)) => {
+                correct += 1;
+                history.wpm[second] += 1;
+            }
+            StepKind::Input(Diff::Error(..) | Diff::Extra(_)) => {
+                errors += 1;
+                history.wpm[second] += 1;
+                history.err[second] += 1;
+            }
+            // The composed character counts as one of the keystrokes.
+            StepKind::Compose(keys) => {
+                history.wpm[second] += keys.chars().count().saturating_sub(1) as u16;
+            }
+            StepKind::Start(word) => word_start = Some((*word, step.instant)),
+            StepKind::Complete(word) => {
+                let len = raw.words[*word].graphemes(true).count() + 1;
+                chars += len;
+                // The space completing the word is a keystroke too.
+                history.wpm[second] += 1;
+                if let Some((_, start)) = word_start.filter(|(started, _)| started == word) {
+                    let minutes = (step.instant - start).as_secs_f32() / 60.0;
+                    if minutes > 0.0 {
+                        word_speeds.push(len as f32 / WORD_LEN / minutes);
+                    }
+                }
+            }
+        }
+    }
+    // Keystrokes per second to raw words per minute.
+    for wpm in &mut history.wpm {
+        *wpm = (*wpm as f32 * 60.0 / WORD_LEN).round() as u16;
+    }
+
+    let wpm = if minutes > 0.0 {
+        chars as f32 / WORD_LEN / minutes
+    } else {
+        0.0
+    };
+    let acc = if correct + errors > 0 {
+        100.0 * correct as f32 / (correct + errors) as f32
+    } else {
+        0.0
+    };
 
     TestResult {
         timestamp: raw.start,
         duration,
         word_set: word_set.into(),
+        mode: Mode::Words,
         word_count: raw.word_count as u32,
         punct: raw.punct,
         numbers: raw.numbers,
+        caps: raw.caps,
+        symbols: raw.symbols,
+        filter: raw.filter.clone(),
+        lazy: raw.lazy,
         wpm,
         acc,
-        cons,
+        cons: consistency(&word_speeds),
         errors,
         quit: raw.quit,
+        tags: Vec::new(),
         history,
     }
 }
 
+/// How even the speed is from word to word, as 100% less the coefficient of variation.
+fn consistency(speeds: &[f32]) -> f32 {
+    if speeds.is_empty() {
+        return 0.0;
+    }
+    let n = speeds.len() as f32;
+    let mean = speeds.iter().sum::<f32>() / n;
+    let variance = speeds.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / n;
+    (100.0 * (1.0 - variance.sqrt() / mean)).max(0.0)
+}
+
 #[derive(Debug)]
 pub struct TestResult {
     pub timestamp: u64,
     pub duration: u32,
     pub word_set: String,
+    pub mode: Mode,
     pub word_count: u32,
     pub punct: bool,
     pub numbers: bool,
+    pub caps: bool,
+    pub symbols: bool,
+    /// Description of the word filters, if any were used.
+    pub filter: Option<String>,
+    pub lazy: bool,
     pub wpm: f32,
     pub acc: f32,
     pub cons: f32,
     pub errors: u32,
     pub quit: bool,
+    pub tags: Vec<String>,
     pub history: History,
 }
 
+impl TestResult {
+    /// The settings personal bests are kept for, e.g. `english_1k, 50 words, punctuation`.
+    pub fn category(&self) -> String {
+        let mut parts = vec![self.word_set.clone()];
+        parts.push(match self.mode {
+            Mode::Words if self.word_count == 1 => "1 word".into(),
+            Mode::Words => format!("{} words", self.word_count),
+        });
+        if self.punct {
+            parts.push("punctuation".into());
+        }
+        if self.numbers {
+            parts.push("numbers".into());
+        }
+        if self.caps {
+            parts.push("caps".into());
+        }
+        if self.symbols {
+            parts.push("symbols".into());
+        }
+        if let Some(filter) = &self.filter {
+            parts.push(filter.clone());
+        }
+        parts.join(", ")
+    }
+}
+
+/// What ends a test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// A set number of words.
+    Words,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Words => write!(f, "words"),
+        }
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "words" => Ok(Self::Words),
+            _ => Err(format!("Invalid mode '{}': must be 'words'", s)),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct History {
     pub wpm: Vec<u16>,
@@ -72,3 +210,154 @@ impl bincode::Decode for History {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::test::Step;
+
+    fn raw(words: &[&str], steps: &[(u64, StepKind)], duration: u64, quit: bool) -> TestRawResult {
+        let start = Instant::now();
+        TestRawResult {
+            words: words.iter().map(|&w| w.into()).collect(),
+            word_count: words.len(),
+            punct: false,
+            numbers: false,
+            caps: false,
+            symbols: false,
+            filter: None,
+            lazy: false,
+            steps: steps
+                .iter()
+                .map(|(ms, kind)| Step {
+                    kind: kind.clone(),
+                    instant: start + Duration::from_millis(*ms),
+                })
+                .collect(),
+            start: 0,
+            duration: Duration::from_millis(duration),
+            quit,
+        }
+    }
+
+    fn correct(s: &str) -> StepKind {
+        StepKind::Input(Diff::Correct(s.into()))
+    }
+
+    #[test]
+    fn completed_words() {
+        let raw = raw(
+            &["ab", "cd"],
+            &[
+                (0, StepKind::Start(0)),
+                (0, correct("a")),
+                (300, correct("b")),
+                (600, StepKind::Complete(0)),
+                (600, StepKind::Start(1)),
+                (1200, correct("c")),
+                (1500, correct("d")),
+                (1800, StepKind::Complete(1)),
+            ],
+            1800,
+            false,
+        );
+        let result = process_raw("set", &raw);
+        // 6 characters with spaces over 0.03 minutes.
+        assert!((result.wpm - 40.0).abs() < 1e-3);
+        assert_eq!(result.acc, 100.0);
+        assert_eq!(result.errors, 0);
+        // Words took 0.6s and 1.2s.
+        assert!((result.cons - 100.0 * (1.0 - 1.0 / 3.0)).abs() < 1e-3);
+        // Keystrokes, spaces included, per second at 12 wpm each.
+        assert_eq!(result.history.wpm, [36, 36]);
+        assert_eq!(result.history.err, [0, 0]);
+    }
+
+    #[test]
+    fn corrections() {
+        let raw = raw(
+            &["ab"],
+            &[
+                (0, StepKind::Start(0)),
+                (0, correct("a")),
+                (100, StepKind::Input(Diff::Error("x".into(), "b".into()))),
+                (200, StepKind::Input(Diff::Extra("y".into()))),
+                (1100, correct("b")),
+                (1200, StepKind::Complete(0)),
+            ],
+            1200,
+            false,
+        );
+        let result = process_raw("set", &raw);
+        assert_eq!(result.errors, 2);
+        assert_eq!(result.acc, 50.0);
+        assert!((result.wpm - 30.0).abs() < 1e-3);
+        assert_eq!(result.history.wpm, [36, 24]);
+        assert_eq!(result.history.err, [2, 0]);
+    }
+
+    #[test]
+    fn composed_keystrokes() {
+        let raw = raw(
+            &["é"],
+            &[
+                (0, StepKind::Start(0)),
+                (0, StepKind::Compose("'e".into())),
+                (0, correct("é")),
+                (600, StepKind::Complete(0)),
+            ],
+            600,
+            false,
+        );
+        let result = process_raw("set", &raw);
+        assert_eq!(result.acc, 100.0);
+        // Two keystrokes for the character and one for the space.
+        assert_eq!(result.history.wpm, [36]);
+        assert!((result.wpm - 40.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn quit_mid_word() {
+        let raw = raw(
+            &["ab", "cd"],
+            &[
+                (0, StepKind::Start(0)),
+                (0, correct("a")),
+                (500, correct("b")),
+                (1000, StepKind::Complete(0)),
+                (1000, StepKind::Start(1)),
+                (1500, correct("c")),
+            ],
+            2000,
+            true,
+        );
+        let result = process_raw("set", &raw);
+        assert!(result.quit);
+        // Only the completed word counts.
+        assert!((result.wpm - 18.0).abs() < 1e-3);
+        assert_eq!(result.acc, 100.0);
+        assert_eq!(result.cons, 100.0);
+        assert_eq!(result.history.wpm, [24, 24]);
+    }
+
+    #[test]
+    fn zero_duration() {
+        let raw = raw(
+            &["a"],
+            &[
+                (0, StepKind::Start(0)),
+                (0, correct("a")),
+                (0, StepKind::Complete(0)),
+            ],
+            0,
+            false,
+        );
+        let result = process_raw("set", &raw);
+        assert_eq!(result.wpm, 0.0);
+        assert_eq!(result.cons, 0.0);
+        assert_eq!(result.acc, 100.0);
+        assert!(!result.wpm.is_nan() && !result.cons.is_nan());
+    }
+}
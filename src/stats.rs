@@ -0,0 +1,116 @@
+use std::collections::BTreeMap;
+
+use crate::result::TestResult;
+
+/// Prints a summary of results, oldest first, with speed and accuracy taken from finished
+/// tests only.
+pub fn print_summary(results: &[TestResult]) {
+    if results.is_empty() {
+        println!("No results.");
+        return;
+    }
+
+    let finished: Vec<_> = results.iter().filter(|r| !r.quit).collect();
+    let time: u64 = results.iter().map(|r| r.duration as u64).sum();
+
+    println!(
+        "Tests: {} ({} finished, {} quit)",
+        results.len(),
+        finished.len(),
+        results.len() - finished.len()
+    );
+    println!("Time typed: {}", format_duration(time));
+    if finished.is_empty() {
+        return;
+    }
+
+    let wpm: Vec<_> = finished.iter().map(|r| r.wpm).collect();
+    let acc: Vec<_> = finished.iter().map(|r| r.acc).collect();
+    println!(
+        "WPM: best {:.1}, average {:.1}, median {:.1}",
+        max(&wpm),
+        mean(&wpm),
+        median(&wpm)
+    );
+    println!(
+        "Accuracy: best {:.1}%, average {:.1}%, median {:.1}%",
+        max(&acc),
+        mean(&acc),
+        median(&acc)
+    );
+
+    for last in [10, 100] {
+        if finished.len() >= last {
+            let recent = &finished[finished.len() - last..];
+            println!(
+                "Last {}: {:.1} wpm, {:.1}% accuracy",
+                last,
+                mean(&recent.iter().map(|r| r.wpm).collect::<Vec<_>>()),
+                mean(&recent.iter().map(|r| r.acc).collect::<Vec<_>>())
+            );
+        }
+    }
+
+    let mut by_set: BTreeMap<&str, Vec<&TestResult>> = BTreeMap::new();
+    for result in &finished {
+        by_set.entry(&result.word_set).or_default().push(result);
+    }
+    println!();
+    println!("By word set");
+    let width = by_set
+        .keys()
+        .map(|set| set.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (set, results) in by_set {
+        let wpm: Vec<_> = results.iter().map(|r| r.wpm).collect();
+        let acc: Vec<_> = results.iter().map(|r| r.acc).collect();
+        println!(
+            "  {:width$}  {:>4} test{}  best {:5.1}  average {:5.1} wpm  {:5.1}%",
+            set,
+            results.len(),
+            if results.len() == 1 { " " } else { "s" },
+            max(&wpm),
+            mean(&wpm),
+            mean(&acc),
+            width = width
+        );
+    }
+}
+
+pub fn mean(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f32>() / values.len() as f32
+}
+
+pub fn median(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+fn max(values: &[f32]) -> f32 {
+    values.iter().copied().fold(0.0, f32::max)
+}
+
+/// Formats seconds as e.g. `1h 12m 5s`.
+fn format_duration(secs: u64) -> String {
+    let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if h > 0 {
+        format!("{}h {}m {}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m {}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{
+    config::{parse_fraction, parse_weights},
+    rand,
+};
+
+/// Placeholder for a word in a symbol pattern.
+const WORD: char = '%';
+
+/// Settings for combining words with programming symbols.
+#[derive(Clone, Debug)]
+pub struct Symbols {
+    /// Fraction of words given a symbol pattern.
+    pub density: f64,
+    /// Patterns and their relative weights.
+    ///
+    /// Each `%` in a pattern takes the next word, so `%::%` joins two words and `#[%]` wraps
+    /// one. Spaces split a pattern into several words, as in `% && %`.
+    pub patterns: Vec<(String, f64)>,
+}
+
+impl Default for Symbols {
+    fn default() -> Self {
+        const PATTERNS: &[(&str, f64)] = &[
+            ("%->%", 2.0),
+            ("%::%", 2.0),
+            ("%.%", 2.0),
+            ("%()", 2.0),
+            ("%;", 2.0),
+            ("&%", 1.0),
+            ("*%", 1.0),
+            ("!%", 1.0),
+            ("%?", 1.0),
+            ("{%}", 1.0),
+            ("[%]", 1.0),
+            ("<%>", 1.0),
+            ("(%)", 1.0),
+            ("#[%]", 1.0),
+            ("$(%)", 1.0),
+            ("${%}", 1.0),
+            ("'%'", 1.0),
+            ("\"%\"", 1.0),
+            ("% => %", 1.0),
+            ("% && %", 1.0),
+            ("% || %", 1.0),
+            ("% == %", 1.0),
+            ("% != %", 1.0),
+            ("% += %", 1.0),
+            ("% <= %", 1.0),
+            ("%: %", 1.0),
+        ];
+
+        Self {
+            density: 0.25,
+            patterns: PATTERNS.iter().map(|&(p, w)| (p.into(), w)).collect(),
+        }
+    }
+}
+
+impl Symbols {
+    /// Reads a `[symbols]` table, with `density` and a `patterns` table of weights. Patterns
+    /// given in `patterns` replace the built-in ones.
+    pub fn from_value(value: &toml::Value) -> Result<Self, String> {
+        let mut symbols = Self::default();
+
+        if let Some(density) = value.get("density") {
+            symbols.density = parse_fraction(density, "density")?;
+        }
+
+        if let Some(patterns) = value.get("patterns") {
+            symbols.patterns = parse_weights(patterns, "patterns", "pattern")?;
+        }
+
+        Ok(symbols)
+    }
+
+    /// Applies patterns to about a `density` fraction of the words taken from `words` until
+    /// there are `count`, counting each space-separated part of a pattern as a word. Fewer are
+    /// returned only if `words` runs out.
+    pub fn apply(&self, mut words: impl Iterator<Item = String>, count: usize) -> Vec<String> {
+        let mut out = Vec::with_capacity(count);
+        while out.len() < count {
+            let word = match words.next() {
+                Some(word) => word,
+                None => break,
+            };
+            // Patterns are left out near the end if their words wouldn't fit.
+            let room = count - out.len();
+            let pattern = match self.choose_pattern() {
+                Some(pattern) if rand::f64() < self.density && size(pattern) <= room => pattern,
+                _ => {
+                    out.push(word);
+                    continue;
+                }
+            };
+
+            let mut filled = String::new();
+            let mut word = Some(word);
+            let mut last = String::new();
+            for c in pattern.chars() {
+                if c == WORD {
+                    // Patterns needing more words than remain reuse the last one.
+                    if let Some(next) = word.take().or_else(|| words.next()) {
+                        last = next;
+                    }
+                    filled.push_str(&last);
+                } else {
+                    filled.push(c);
+                }
+            }
+            out.extend(filled.split_whitespace().map(String::from));
+            // Patterns without a placeholder don't use the word.
+            if let Some(word) = word {
+                out.push(word);
+            }
+        }
+        out
+    }
+
+    fn choose_pattern(&self) -> Option<&str> {
+        rand::choose_weighted(&self.patterns).map(String::as_str)
+    }
+}
+
+/// Number of words a pattern makes, including the word it is given if it has no placeholder.
+fn size(pattern: &str) -> usize {
+    pattern.split_whitespace().count() + usize::from(!pattern.contains(WORD))
+}
@@ -6,45 +6,65 @@ use std::{
 };
 
 use termion::{event::Key, input::TermRead};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::{theme::Theme, ui::WordsRender, words::WordSet};
+use crate::{
+    compose::Composer,
+    lazy::Lazy,
+    theme::Theme,
+    ui::WordsRender,
+    words::{Direction, Options, WordSet},
+};
 
 pub fn run_test(
     word_set: &WordSet,
     word_count: usize,
-    punct: bool,
-    numbers: bool,
+    options: &Options,
     theme: Theme,
+    direction: Direction,
 ) -> io::Result<Option<TestRawResult>> {
-    let words = word_set.choose_with(word_count, punct, numbers);
+    // Input is compared in composed form, so precomposed and combining input match alike.
+    let words: Vec<String> = word_set
+        .choose_with(word_count, options)
+        .iter()
+        .map(|word| word.nfc().collect())
+        .collect();
     let words: Vec<_> = words.iter().map(|x| &**x).collect();
-    Test::new(&words, punct, numbers, theme).run()
+    Test::new(&words, word_count, options, theme, direction).run()
 }
 
 struct Test<'a> {
     words: &'a [&'a str],
-    punct: bool,
-    numbers: bool,
+    /// The number of words asked for, recorded in the result.
+    word_count: usize,
+    options: &'a Options,
     theme: Theme,
+    direction: Direction,
 
     timer: Timer,
     input: String,
     word: usize,
-    pos: usize,
 }
 
 impl<'a> Test<'a> {
-    fn new(words: &'a [&'a str], punct: bool, numbers: bool, theme: Theme) -> Self {
+    fn new(
+        words: &'a [&'a str],
+        word_count: usize,
+        options: &'a Options,
+        theme: Theme,
+        direction: Direction,
+    ) -> Self {
         assert!(!words.is_empty());
         Self {
             words,
-            punct,
-            numbers,
+            word_count,
+            options,
             theme,
+            direction,
             timer: Timer::new(),
             input: String::new(),
             word: 0,
-            pos: 0,
         }
     }
 
@@ -52,38 +72,68 @@ impl<'a> Test<'a> {
         let mut steps = Vec::new();
 
         let (send, recv) = mpsc::channel();
+        let mut composer = self.options.compose.clone().map(Composer::new);
+        let layout = self.options.layout.clone();
         thread::spawn(move || {
             let stdin = &mut io::stdin();
             loop {
-                match send.send(stdin.keys().find_map(Result::ok).unwrap()) {
-                    Ok(_) => (),
-                    Err(_) => return,
+                let key = match (stdin.keys().find_map(Result::ok).unwrap(), &layout) {
+                    (Key::Char(c), Some(layout)) => Key::Char(layout.map(c)),
+                    (key, _) => key,
+                };
+                // Backspace first cancels an unfinished sequence.
+                if key == Key::Backspace && composer.as_mut().is_some_and(Composer::cancel) {
+                    continue;
+                }
+
+                let inputs = match (&mut composer, key) {
+                    (Some(composer), Key::Char(c)) => composer
+                        .feed(c)
+                        .into_iter()
+                        .map(|(c, keys)| Input {
+                            key: Key::Char(c),
+                            composed: (keys.chars().count() > 1).then_some(keys),
+                        })
+                        .collect(),
+                    (_, key) => vec![Input {
+                        key,
+                        composed: None,
+                    }],
+                };
+                for input in inputs {
+                    if send.send(input).is_err() {
+                        return;
+                    }
                 }
             }
         });
 
-        let mut render = WordsRender::new(self.words, self.theme)?;
+        let mut render = WordsRender::new(self.words, self.theme, self.direction)?;
         render.start()?;
         let quit = loop {
             render.render()?;
 
-            let key = recv.recv_timeout(Duration::from_millis(200));
-            if key.is_err() {
+            let input = recv.recv_timeout(Duration::from_millis(200));
+            if input.is_err() {
                 continue;
             }
+            let Input { key, composed } = input.unwrap();
 
-            match key.unwrap() {
+            match key {
                 Key::Ctrl('c' | 'd' | 'q' | 'z') | Key::Esc => break true,
                 Key::Char(c) => {
                     if !self.timer.running() {
                         self.timer.start();
                         steps.push(Step::start(0));
                     }
+                    if let Some(keys) = composed {
+                        steps.push(Step::compose(keys));
+                    }
 
-                    if c == ' ' && self.input == self.words[self.word] {
+                    let target = self.words[self.word];
+                    if c == ' ' && diff_word(&self.input, target, self.options.lazy.as_ref()).1 {
                         steps.push(Step::complete(self.word));
                         self.input.clear();
-                        self.pos = 0;
                         self.word += 1;
 
                         // Test over.
@@ -95,20 +145,24 @@ impl<'a> Test<'a> {
                         render.next_word()?;
                     } else {
                         self.input.push(c);
-                        let diff = diff_at(&self.input, self.words[self.word], self.pos);
-                        match diff {
-                            Diff::Correct(c) => render.correct(c)?,
-                            Diff::Error(_, c) => render.error(c)?,
-                            Diff::Extra(c) => render.extra(c)?,
+                        let (mut diffs, _) =
+                            diff_word(&self.input, target, self.options.lazy.as_ref());
+                        render.update(&diffs);
+                        // A keystroke changes the last grapheme, whether it starts a new one or
+                        // adds a combining mark to it.
+                        if let Some(diff) = diffs.pop() {
+                            steps.push(Step::input(diff));
                         }
-                        steps.push(Step::input(diff));
-                        self.pos += 1;
                     }
                 }
-                Key::Backspace if self.pos > 0 => {
+                Key::Backspace if !self.input.is_empty() => {
                     self.input.pop();
-                    self.pos -= 1;
-                    render.undo()?;
+                    let (diffs, _) = diff_word(
+                        &self.input,
+                        self.words[self.word],
+                        self.options.lazy.as_ref(),
+                    );
+                    render.update(&diffs);
                 }
                 _ => (),
             }
@@ -116,9 +170,14 @@ impl<'a> Test<'a> {
         render.end()?;
 
         Ok(self.timer.stop().map(|(start, duration)| TestRawResult {
-            word_count: self.words.len(),
-            punct: self.punct,
-            numbers: self.numbers,
+            words: self.words.iter().map(|&word| word.into()).collect(),
+            word_count: self.word_count,
+            punct: self.options.punct.is_some(),
+            numbers: self.options.numbers.is_some(),
+            caps: self.options.caps.is_some(),
+            symbols: self.options.symbols.is_some(),
+            filter: (!self.options.filter.is_empty()).then(|| self.options.filter.to_string()),
+            lazy: self.options.lazy.is_some(),
             steps,
             start,
             duration,
@@ -127,11 +186,24 @@ impl<'a> Test<'a> {
     }
 }
 
+/// A key to handle, with the keystrokes that typed it if it was composed from several.
+struct Input {
+    key: Key,
+    composed: Option<String>,
+}
+
 #[derive(Debug)]
 pub struct TestRawResult {
+    pub words: Vec<String>,
+    /// The requested number of words, which modifiers such as punctuation may not keep to.
     pub word_count: usize,
     pub punct: bool,
     pub numbers: bool,
+    pub caps: bool,
+    pub symbols: bool,
+    /// Description of the word filters, if any were used.
+    pub filter: Option<String>,
+    pub lazy: bool,
     pub steps: Vec<Step>,
     pub start: u64,
     pub duration: Duration,
@@ -144,18 +216,22 @@ pub struct Step {
     pub instant: Instant,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum StepKind {
     Input(Diff),
+    /// Keystrokes composing the character of the next input.
+    Compose(String),
     Start(usize),
     Complete(usize),
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+/// Comparison of a typed grapheme cluster with the target.
+#[derive(Clone, PartialEq, Debug)]
 pub enum Diff {
-    Correct(char),
-    Error(char, char),
-    Extra(char),
+    Correct(String),
+    /// The typed and target graphemes.
+    Error(String, String),
+    Extra(String),
 }
 
 impl Step {
@@ -167,6 +243,14 @@ impl Step {
         }
     }
 
+    #[inline]
+    fn compose(keys: String) -> Self {
+        Self {
+            kind: StepKind::Compose(keys),
+            instant: Instant::now(),
+        }
+    }
+
     #[inline]
     fn start(word: usize) -> Self {
         Self {
@@ -214,13 +298,124 @@ impl Timer {
     }
 }
 
-fn diff_at(input: &str, target: &str, i: usize) -> Diff {
-    let a = input.chars().nth(i);
-    let b = target.chars().nth(i);
-    match (a, b) {
-        (Some(c), Some(d)) if c == d => Diff::Correct(c),
-        (Some(c), Some(d)) => Diff::Error(c, d),
-        (Some(c), None) => Diff::Extra(c),
-        (_, _) => unreachable!(),
+/// Compares typed input with the target word, returning a diff for each target grapheme cluster
+/// reached, then one for each extra typed grapheme, and whether the input matches the whole word.
+///
+/// Both are compared in composed (NFC) form. In lazy mode, a target grapheme may also be typed as
+/// one of its alternatives, which may span several graphemes (e.g. `ss` for `ß`). A target
+/// grapheme counts as correct while the input ends partway through it, as when a combining mark
+/// is still to be typed.
+fn diff_word(input: &str, target: &str, lazy: Option<&Lazy>) -> (Vec<Diff>, bool) {
+    let input: String = input.nfc().collect();
+    let input: Vec<_> = input.graphemes(true).collect();
+    let target: Vec<_> = target.graphemes(true).collect();
+
+    let mut diffs = Vec::new();
+    let mut i = 0;
+    let mut partial = false;
+    for &expected in &target {
+        if i == input.len() {
+            break;
+        }
+
+        let mut candidates = vec![expected.to_string()];
+        if let Some(lazy) = lazy {
+            candidates.extend(lazy.alternatives(expected));
+        }
+        let matches: Vec<_> = candidates
+            .iter()
+            .filter_map(|candidate| match_at(&input[i..], candidate))
+            .collect();
+
+        // The longest alternative wins, so `--` is not cut short by `-`.
+        let full = matches
+            .iter()
+            .filter_map(|m| match m {
+                Match::Full(len) => Some(*len),
+                Match::Partial => None,
+            })
+            .max();
+        if let Some(len) = full {
+            diffs.push(Diff::Correct(expected.into()));
+            i += len;
+        } else if !matches.is_empty() {
+            diffs.push(Diff::Correct(expected.into()));
+            i = input.len();
+            partial = true;
+        } else {
+            diffs.push(Diff::Error(input[i].into(), expected.into()));
+            i += 1;
+        }
+    }
+    diffs.extend(input[i..].iter().map(|&extra| Diff::Extra(extra.into())));
+
+    let complete = !partial
+        && diffs.len() == target.len()
+        && diffs.iter().all(|d| matches!(d, Diff::Correct(_)));
+    (diffs, complete)
+}
+
+enum Match {
+    /// The candidate was typed with this many graphemes.
+    Full(usize),
+    /// The input ends partway through the candidate.
+    Partial,
+}
+
+/// Matches the start of the input against a candidate for the next target grapheme.
+fn match_at(input: &[&str], candidate: &str) -> Option<Match> {
+    let mut typed = String::new();
+    for (i, grapheme) in input.iter().enumerate() {
+        typed.push_str(grapheme);
+        if typed == candidate {
+            return Some(Match::Full(i + 1));
+        }
+        if !is_partial(&typed, candidate) {
+            return None;
+        }
+    }
+    Some(Match::Partial)
+}
+
+/// Whether typed text is the start of the expected text, comparing decomposed forms.
+fn is_partial(typed: &str, expected: &str) -> bool {
+    let mut expected = expected.nfd();
+    typed.nfd().all(|c| expected.next() == Some(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn lazy(target: &str, alternatives: &[&str]) -> Lazy {
+        Lazy {
+            strip_accents: false,
+            equivalents: HashMap::from([(
+                target.to_string(),
+                alternatives.iter().map(|a| a.to_string()).collect(),
+            )]),
+        }
+    }
+
+    #[test]
+    fn longest_alternative_wins() {
+        for alternatives in [["ss", "s"], ["s", "ss"]] {
+            let lazy = lazy("ß", &alternatives);
+            assert!(diff_word("strasse", "straße", Some(&lazy)).1);
+            assert!(diff_word("strase", "straße", Some(&lazy)).1);
+        }
+        for alternatives in [["-", "--"], ["--", "-"]] {
+            let lazy = lazy("—", &alternatives);
+            assert!(diff_word("a--b", "a—b", Some(&lazy)).1);
+            assert!(diff_word("a-b", "a—b", Some(&lazy)).1);
+            let (diffs, complete) = diff_word("a--", "a—b", Some(&lazy));
+            assert!(!complete);
+            assert_eq!(
+                diffs,
+                [Diff::Correct("a".into()), Diff::Correct("—".into())]
+            );
+        }
     }
 }
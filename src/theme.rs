@@ -2,23 +2,32 @@ use std::{collections::HashMap, fmt, str::FromStr};
 
 use termion::color;
 
+/// Themes embedded in the binary, in the same format as the config.
+pub const BUILTIN_THEMES: &str = include_str!("../themes.toml");
+
 #[derive(Default, Debug)]
 pub struct Themes {
     themes: HashMap<String, Theme>,
 }
 
 impl Themes {
+    /// Reads the `[themes.<name>]` tables of the config over the built-in themes.
     pub fn from_value(value: &toml::Value) -> Result<Self, ThemeError> {
-        let theme_table = value
-            .get("themes")
-            .ok_or(ThemeError::MissingThemeTable)?
-            .as_table()
-            .ok_or(ThemeError::InvalidThemeTable)?;
+        let builtin = BUILTIN_THEMES
+            .parse::<toml::Value>()
+            .expect("Fatal: built-in themes are invalid");
 
         let mut themes = HashMap::new();
-        for (name, theme) in theme_table.iter() {
-            let theme = Theme::from_value(theme).map_err(|e| ThemeError::Load(name.clone(), e))?;
-            themes.insert(name.clone(), theme);
+        for value in [&builtin, value] {
+            let theme_table = match value.get("themes") {
+                Some(table) => table.as_table().ok_or(ThemeError::InvalidThemeTable)?,
+                None => continue,
+            };
+            for (name, theme) in theme_table.iter() {
+                let theme =
+                    Theme::from_value(theme).map_err(|e| ThemeError::Load(name.clone(), e))?;
+                themes.insert(name.clone(), theme);
+            }
         }
 
         Ok(Self { themes })
@@ -36,7 +45,6 @@ impl Themes {
 #[derive(Debug)]
 pub enum ThemeError {
     Load(String, ThemeLoadError),
-    MissingThemeTable,
     InvalidThemeTable,
 }
 
@@ -44,7 +52,6 @@ impl fmt::Display for ThemeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Load(name, e) => write!(f, "Error loading theme '{}': {:?}", name, e),
-            Self::MissingThemeTable => write!(f, "No theme table found"),
             Self::InvalidThemeTable => write!(
                 f,
                 "Invalid theme table: must be formatted as [themes.<theme-name>] (...)"
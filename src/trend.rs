@@ -0,0 +1,236 @@
+use crate::{result::TestResult, stats::mean};
+
+const DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// Settings for analysing progress over time.
+pub struct TrendOptions {
+    /// Number of tests in each rolling average.
+    pub window: usize,
+    /// Number of most recent tests checked for a plateau.
+    pub plateau: usize,
+    /// Speed to estimate the arrival of, in words per minute.
+    pub target: Option<f32>,
+}
+
+/// A least-squares line fitted to points.
+pub struct Regression {
+    pub slope: f64,
+    pub intercept: f64,
+    /// Standard error of the slope, infinite with too few points to tell.
+    pub slope_error: f64,
+}
+
+impl Regression {
+    pub fn fit(points: &[(f64, f64)]) -> Option<Self> {
+        let n = points.len() as f64;
+        if points.len() < 2 {
+            return None;
+        }
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        if sxx == 0.0 {
+            return None;
+        }
+        let sxy: f64 = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+        let slope = sxy / sxx;
+        let intercept = mean_y - slope * mean_x;
+
+        let slope_error = if points.len() > 2 {
+            let residuals: f64 = points
+                .iter()
+                .map(|(x, y)| (y - intercept - slope * x).powi(2))
+                .sum();
+            (residuals / (n - 2.0) / sxx).sqrt()
+        } else {
+            f64::INFINITY
+        };
+
+        Some(Self {
+            slope,
+            intercept,
+            slope_error,
+        })
+    }
+
+    /// Whether the slope is positive at roughly 95% confidence.
+    pub fn is_improving(&self) -> bool {
+        self.slope > 2.0 * self.slope_error
+    }
+}
+
+/// Averages of each run of `window` consecutive values.
+pub fn rolling(values: &[f32], window: usize) -> Vec<f32> {
+    values.windows(window.max(1)).map(mean).collect()
+}
+
+/// Prints rolling averages, the trend of speed and accuracy, whether progress has stalled, and
+/// when the target speed should be reached at the recent rate of progress. Results must be
+/// oldest first; quit tests are left out.
+pub fn print_trend(results: &[TestResult], options: &TrendOptions) {
+    let finished: Vec<_> = results.iter().filter(|r| !r.quit).collect();
+    if finished.len() < 2 {
+        println!("Not enough finished tests to analyse; at least 2 are needed.");
+        return;
+    }
+
+    let wpm: Vec<_> = finished.iter().map(|r| r.wpm).collect();
+    let acc: Vec<_> = finished.iter().map(|r| r.acc).collect();
+    let window = options.window.clamp(1, finished.len());
+    let rolling_wpm = rolling(&wpm, window);
+    let rolling_acc = rolling(&acc, window);
+    let current = *rolling_wpm.last().unwrap();
+
+    println!("Tests: {} finished", finished.len());
+    print!(
+        "Rolling average of the last {}: {:.1} wpm, {:.1}% accuracy",
+        window,
+        current,
+        rolling_acc.last().unwrap()
+    );
+    if rolling_wpm.len() > window {
+        let previous = rolling_wpm[rolling_wpm.len() - 1 - window];
+        print!(" ({:+.1} wpm on the {} before)", current - previous, window);
+    }
+    println!();
+
+    let first = finished[0].timestamp as f64;
+    let by_day = |values: &[f32]| -> Vec<(f64, f64)> {
+        finished
+            .iter()
+            .zip(values)
+            .map(|(r, &v)| ((r.timestamp as f64 - first) / DAY, v as f64))
+            .collect()
+    };
+    let by_test = |values: &[f32]| -> Vec<(f64, f64)> {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| (i as f64, v as f64))
+            .collect()
+    };
+
+    let wpm_by_day = Regression::fit(&by_day(&wpm));
+    let wpm_by_test = Regression::fit(&by_test(&wpm));
+    let acc_by_test = Regression::fit(&by_test(&acc));
+    if let Some(trend) = &wpm_by_test {
+        print!("Speed trend: {:+.2} wpm per test", trend.slope);
+        if let Some(trend) = &wpm_by_day {
+            print!(", {:+.2} wpm per day", trend.slope);
+        }
+        println!();
+    }
+    if let Some(trend) = &acc_by_test {
+        println!("Accuracy trend: {:+.3}% per test", trend.slope);
+    }
+
+    let recent = &wpm[wpm.len().saturating_sub(options.plateau)..];
+    let plateau_trend = Regression::fit(&by_test(recent))
+        .filter(|trend| recent.len() == options.plateau && trend.slope_error.is_finite());
+    match plateau_trend {
+        Some(trend) if !trend.is_improving() => println!(
+            "Plateau: no significant improvement over the last {} tests ({:+.2} wpm per test).",
+            options.plateau, trend.slope
+        ),
+        Some(trend) => println!(
+            "Improving over the last {} tests ({:+.2} wpm per test).",
+            options.plateau, trend.slope
+        ),
+        None => println!(
+            "Plateau: not enough tests to tell; {} are needed.",
+            options.plateau.max(3)
+        ),
+    }
+
+    if let Some(target) = options.target {
+        if current >= target {
+            println!("Target of {:.0} wpm reached.", target);
+            return;
+        }
+        // The recent trend, as a plateau makes the earlier progress irrelevant.
+        let recent = finished.len().saturating_sub(options.plateau);
+        match Regression::fit(&by_day(&wpm)[recent..]) {
+            Some(trend) if trend.is_improving() => {
+                let last = (finished.last().unwrap().timestamp as f64 - first) / DAY;
+                let days = ((target as f64 - trend.intercept) / trend.slope - last).max(0.0);
+                let date = civil_date(((first + (last + days) * DAY) / DAY) as i64);
+                println!(
+                    "Target of {:.0} wpm estimated in about {:.0} days ({}).",
+                    target, days, date
+                );
+            }
+            _ => println!(
+                "Target of {:.0} wpm: no estimate, as speed is not improving significantly.",
+                target
+            ),
+        }
+    }
+}
+
+/// Formats days since the Unix epoch as a `YYYY-MM-DD` date.
+pub fn civil_date(days: i64) -> String {
+    // Howard Hinnant's days-to-civil algorithm.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Parses a `YYYY-MM-DD` date as days since the Unix epoch.
+pub fn parse_date(s: &str) -> Result<i64, String> {
+    let error = || {
+        format!(
+            "Invalid date '{}': must be a calendar date as YYYY-MM-DD",
+            s
+        )
+    };
+    let mut parts = s
+        .split('-')
+        .map(|part| part.parse::<i64>().map_err(|_| error()));
+    let (year, month, day) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(year), Some(month), Some(day), None) => (year?, month?, day?),
+        _ => return Err(error()),
+    };
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(error());
+    }
+
+    // Howard Hinnant's days-from-civil algorithm, the inverse of `civil_date`.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    // Days past the end of the month, such as February 30, roll over into the next.
+    if civil_date(days) != format!("{:04}-{:02}-{:02}", year, month, day) {
+        return Err(error());
+    }
+    Ok(days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_round_trip() {
+        for days in [-719_468, -1, 0, 59, 11_016, 20_744, 2_932_896] {
+            assert_eq!(parse_date(&civil_date(days)), Ok(days));
+        }
+        assert_eq!(parse_date("2024-02-29"), Ok(19_782));
+        for invalid in ["2023-02-29", "2024-13-01", "2024-1-1x", "2024-01", "today"] {
+            assert!(parse_date(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -6,9 +6,11 @@ use termion::{
     screen::AlternateScreen,
     style,
 };
-use unicode_width::UnicodeWidthChar;
+use unicode_bidi::{BidiInfo, Level};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-use crate::theme::Theme;
+use crate::{test::Diff, theme::Theme, words::Direction};
 
 /// Interface for rendering the typing box.
 pub struct WordsRender {
@@ -20,25 +22,23 @@ pub struct WordsRender {
     lines: Vec<Line>,
     /// Current word.
     word: usize,
-    /// Current line.
-    line: usize,
-    /// Current position on line.
-    pos: usize,
     /// Styling for the test.
     theme: Theme,
+    /// Whether lines flow from the right.
+    rtl: bool,
 }
 
 impl WordsRender {
-    pub fn new(words: &[&str], theme: Theme) -> io::Result<Self> {
+    pub fn new(words: &[&str], theme: Theme, direction: Direction) -> io::Result<Self> {
         let stdout = io::stdout().into_raw_mode()?;
+        let rtl = direction == Direction::Rtl;
         let mut render = Self {
             screen: AlternateScreen::from(stdout),
-            words: words.iter().map(|&word| word.into()).collect(),
+            words: words.iter().map(|&word| Word::new(word, rtl)).collect(),
             lines: Vec::new(),
             word: 0,
-            line: 0,
-            pos: 0,
             theme,
+            rtl,
         };
         render.update_lines()?;
         Ok(render)
@@ -55,48 +55,32 @@ impl WordsRender {
         self.flush()
     }
 
-    pub fn correct(&mut self, c: char) -> io::Result<()> {
-        self.get_word_mut().push(c, Style::Correct);
-        self.cursor_forward()
-    }
-
-    pub fn error(&mut self, c: char) -> io::Result<()> {
-        self.get_word_mut().push(c, Style::Error);
-        self.cursor_forward()
-    }
-
-    pub fn extra(&mut self, c: char) -> io::Result<()> {
-        self.get_word_mut().push(c, Style::Extra);
-        self.cursor_forward()
-    }
-
-    pub fn undo(&mut self) -> io::Result<()> {
-        if self.get_word_mut().pop() {
-            self.cursor_back()?;
-        }
-        Ok(())
+    /// Restyles the current word from the comparison of its input with the target.
+    pub fn update(&mut self, diffs: &[Diff]) {
+        self.get_word_mut().update(diffs);
     }
 
     pub fn next_word(&mut self) -> io::Result<()> {
         self.word += 1;
-        self.cursor_forward()
+        Ok(())
     }
 
     pub fn render(&mut self) -> io::Result<()> {
         self.update_lines()?;
         let (col, row) = termion::terminal_size()?;
         let width = col / 2;
+        let left = width - width / 2;
 
         write!(self.screen, "{}", clear::All)?;
         self.bg()?;
         for (i, line) in self.lines.iter().enumerate() {
-            write!(
-                self.screen,
-                "{}",
-                cursor::Goto(width - width / 2, row / 2 + i as u16),
-            )?;
+            let mut offset = 0;
             for word in &self.words[line.start..line.end] {
-                for &(c, style) in &word.chars {
+                let x = left + self.word_x(offset, word.width(), width as usize) as u16;
+                write!(self.screen, "{}", cursor::Goto(x, row / 2 + i as u16))?;
+                offset += word.width() + 1;
+
+                for (c, style) in word.visual() {
                     match style {
                         Style::Correct => {
                             write!(self.screen, "{}{}", color::Fg(self.theme.correct), c)?;
@@ -119,50 +103,48 @@ impl WordsRender {
                         }
                     }
                 }
-                write!(self.screen, " ")?;
             }
         }
+        let (line, pos) = self.cursor(width as usize);
         write!(
             self.screen,
             "{}",
-            cursor::Goto(
-                width - width / 2 + self.pos as u16,
-                row / 2 + self.line as u16
-            )
+            cursor::Goto(left + pos as u16, row / 2 + line as u16)
         )?;
         self.flush()
     }
 
-    fn update_lines(&mut self) -> io::Result<()> {
-        let (col, _) = termion::terminal_size()?;
-        let width = col / 2;
-        self.lines = wrap(&self.words, width as usize);
-        Ok(())
+    /// Column of a word within the box, given its offset from the start of its line.
+    fn word_x(&self, offset: usize, word_width: usize, width: usize) -> usize {
+        if self.rtl {
+            width.saturating_sub(offset + word_width)
+        } else {
+            offset
+        }
     }
 
-    fn cursor_forward(&mut self) -> io::Result<()> {
-        self.update_lines()?;
-        let c = self.get_word().last().unwrap();
-        let width = c.width().unwrap();
-        if self.pos + width < self.lines[self.line].len {
-            self.pos += width;
-        } else if self.line < self.lines.len() - 1 {
-            self.line += 1;
-            self.pos = self.get_word().width();
-        }
-        Ok(())
+    /// Line and column of the caret, just after the typed part of the current word.
+    fn cursor(&self, width: usize) -> (usize, usize) {
+        let line = self
+            .lines
+            .iter()
+            .position(|line| self.word < line.end)
+            .unwrap_or(self.lines.len() - 1);
+        let offset: usize = self.words[self.lines[line].start..self.word]
+            .iter()
+            .map(|word| word.width() + 1)
+            .sum();
+        let word = self.get_word();
+        (
+            line,
+            self.word_x(offset, word.width(), width) + word.caret(),
+        )
     }
 
-    fn cursor_back(&mut self) -> io::Result<()> {
-        self.update_lines()?;
-        let c = self.get_word().last().unwrap();
-        let width = c.width().unwrap();
-        if self.pos >= width {
-            self.pos -= width;
-        } else if self.line > 0 {
-            self.line -= 1;
-            self.pos = self.lines[self.line].len;
-        }
+    fn update_lines(&mut self) -> io::Result<()> {
+        let (col, _) = termion::terminal_size()?;
+        let width = col / 2;
+        self.lines = wrap(&self.words, width as usize);
         Ok(())
     }
 
@@ -188,55 +170,114 @@ impl WordsRender {
 
 #[derive(Debug)]
 struct Word {
-    initial: Vec<char>,
-    chars: Vec<(char, Style)>,
-    pos: usize,
+    /// Grapheme clusters of the target word.
+    initial: Vec<String>,
+    /// Grapheme clusters as displayed, with typed extra ones after the target.
+    graphemes: Vec<(String, Style)>,
+    /// Number of graphemes typed.
+    typed: usize,
+    /// Paragraph direction, as a bidi embedding level.
+    base: Level,
+    /// Embedding level of each grapheme, odd for right-to-left.
+    levels: Vec<Level>,
+    /// Indices of the graphemes in display order, from left to right.
+    order: Vec<usize>,
 }
 
 impl Word {
-    fn width(&self) -> usize {
-        self.chars.iter().map(|(c, _)| c.width().unwrap()).sum()
+    fn new(s: &str, rtl: bool) -> Self {
+        let initial: Vec<_> = s.graphemes(true).map(printable).collect();
+        let mut word = Self {
+            graphemes: initial.iter().map(|g| (g.clone(), Style::Empty)).collect(),
+            initial,
+            typed: 0,
+            base: if rtl { Level::rtl() } else { Level::ltr() },
+            levels: Vec::new(),
+            order: Vec::new(),
+        };
+        word.reorder();
+        word
     }
 
-    fn last(&self) -> Option<char> {
-        self.chars.last().map(|(c, _)| *c)
+    fn width(&self) -> usize {
+        self.graphemes.iter().map(|(g, _)| g.width()).sum()
     }
 
-    fn push(&mut self, c: char, style: Style) {
-        match self.chars.get_mut(self.pos) {
-            Some(e) => *e = (c, style),
-            None => self.chars.push((c, style)),
-        }
-        self.pos += 1;
+    /// Graphemes in display order, from left to right.
+    fn visual(&self) -> impl Iterator<Item = &(String, Style)> {
+        self.order.iter().map(|&i| &self.graphemes[i])
     }
 
-    fn pop(&mut self) -> bool {
-        if self.pos > 0 {
-            match self.chars.get_mut(self.pos - 1) {
-                Some(e) => {
-                    if self.pos - 1 < self.initial.len() {
-                        *e = (self.initial[self.pos - 1], Style::Empty);
-                    } else {
-                        self.chars.pop();
-                    }
-                    self.pos -= 1;
-                    true
-                }
-                None => false,
+    /// Column of the caret from the left of the word: after the last typed grapheme in its own
+    /// direction, so on its left side for right-to-left text.
+    fn caret(&self) -> usize {
+        let last = match self.typed.checked_sub(1) {
+            Some(last) => last,
+            None if self.base.is_rtl() => return self.width(),
+            None => return 0,
+        };
+
+        let mut x = 0;
+        for &i in &self.order {
+            let width = self.graphemes[i].0.width();
+            if i == last {
+                return if self.levels[i].is_rtl() {
+                    x
+                } else {
+                    x + width
+                };
             }
-        } else {
-            false
+            x += width;
         }
+        x
+    }
+
+    /// Resolves the display order with the Unicode bidi algorithm, so that digits and
+    /// left-to-right runs within right-to-left words read correctly.
+    fn reorder(&mut self) {
+        let text: String = self.graphemes.iter().map(|(g, _)| &**g).collect();
+        let info = BidiInfo::new(&text, Some(self.base));
+
+        let mut start = 0;
+        self.levels = self
+            .graphemes
+            .iter()
+            .map(|(g, _)| {
+                let level = info.levels.get(start).copied().unwrap_or(self.base);
+                start += g.len();
+                level
+            })
+            .collect();
+        self.order = BidiInfo::reorder_visual(&self.levels);
+    }
+
+    fn update(&mut self, diffs: &[Diff]) {
+        self.graphemes = diffs
+            .iter()
+            .map(|diff| match diff {
+                Diff::Correct(g) => (printable(g), Style::Correct),
+                Diff::Error(_, g) => (printable(g), Style::Error),
+                Diff::Extra(g) => (printable(g), Style::Extra),
+            })
+            .chain(
+                self.initial
+                    .iter()
+                    .skip(diffs.len())
+                    .map(|g| (g.clone(), Style::Empty)),
+            )
+            .collect();
+        self.typed = diffs.len();
+        self.reorder();
     }
 }
 
-impl From<&str> for Word {
-    fn from(s: &str) -> Self {
-        Self {
-            initial: s.chars().collect(),
-            chars: s.chars().map(|c| (c, Style::Empty)).collect(),
-            pos: 0,
-        }
+/// Replaces graphemes that would break the layout, such as control characters or a lone
+/// combining mark, with a visible placeholder.
+fn printable(grapheme: &str) -> String {
+    if grapheme.chars().any(char::is_control) || grapheme.width() == 0 {
+        char::REPLACEMENT_CHARACTER.into()
+    } else {
+        grapheme.into()
     }
 }
 
@@ -254,8 +295,6 @@ struct Line {
     start: usize,
     /// Index of word ending the line.
     end: usize,
-    /// Length of the line in chars.
-    len: usize,
 }
 
 fn wrap(words: &[Word], width: usize) -> Vec<Line> {
@@ -265,11 +304,7 @@ fn wrap(words: &[Word], width: usize) -> Vec<Line> {
     for (i, word) in words.iter().enumerate() {
         let word_width = word.width();
         if i > start && line_width + word_width > width {
-            lines.push(Line {
-                start,
-                end: i,
-                len: line_width,
-            });
+            lines.push(Line { start, end: i });
             start = i;
             line_width = 0;
         }
@@ -278,7 +313,6 @@ fn wrap(words: &[Word], width: usize) -> Vec<Line> {
     lines.push(Line {
         start,
         end: words.len(),
-        len: line_width,
     });
     lines
 }
@@ -1,53 +1,843 @@
 use std::{
+    collections::HashMap,
     fmt,
-    fs::File,
-    io::{self, BufRead},
+    fs::{self, File},
+    io::{self, BufRead, Read},
+    iter,
     path::{Path, PathBuf},
+    str::FromStr,
 };
 
-use crate::rand;
+use crate::{
+    caps::Caps,
+    compose::Compose,
+    layout::Layout,
+    lazy::Lazy,
+    lint::{self, Entry, Issue, Location},
+    markov::{self, Model},
+    numbers::Numbers,
+    punct, rand,
+    symbols::Symbols,
+};
+
+/// Word sets embedded in the binary, as names and file contents.
+pub const BUILTIN_SETS: &[(&str, &str)] = &[("english_1k", include_str!("../sets/english_1k"))];
+
+/// Where the words of a named set come from.
+#[derive(Clone, Debug)]
+pub enum SetSource {
+    /// A word set file.
+    File(PathBuf),
+    /// A word set embedded in the binary, given as its name in [`BUILTIN_SETS`].
+    Builtin(&'static str),
+    /// A mix of other sets, given as names and relative weights.
+    Composite(Vec<(String, f64)>),
+    /// Pseudo-text from an n-gram model trained on a corpus file.
+    Markov {
+        corpus: PathBuf,
+        level: markov::Level,
+        order: usize,
+    },
+}
 
 pub struct WordSet {
+    pub meta: Metadata,
     words: Vec<String>,
+    /// Running totals of the word weights, present if the set has a weight column.
+    cumulative: Option<Vec<f64>>,
+    /// Generates the words instead, for sets trained on a corpus.
+    model: Option<Model>,
+    /// Problems found in the word set file when loading it.
+    pub issues: Vec<Issue>,
 }
 
 impl WordSet {
+    /// Loads a word set, picking the format by extension or, failing that, by content:
+    ///
+    /// - A monkeytype language file (`.json`, or content starting with `{`), with a `words`
+    ///   array and optionally `name`, `bcp47` and `rightToLeft`.
+    /// - A CSV frequency list (`.csv`), with `word,count` lines and an optional header line.
+    /// - Otherwise, one word per line after an optional metadata header. A line may carry a
+    ///   second, whitespace-separated column holding the word's frequency or weight (e.g.
+    ///   `the\t56271872`).
+    ///
+    /// If any line is weighted, words are sampled proportionally to their weight, with
+    /// unweighted lines counting as `1`.
+    ///
+    /// The words are checked with [`lint::check`], and entries that can't be typed, such as blank
+    /// lines, are skipped. The problems found are kept in `issues`.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, WordSetError> {
-        let file = File::open(&path)
-            .map_err(|e| WordSetError::Open(path.as_ref().into(), e.to_string()))?;
-        let words: Vec<_> = io::BufReader::new(file)
-            .lines()
-            .filter_map(Result::ok)
-            .map(|s| s.trim().into())
-            .collect();
-        Ok(Self { words })
+        let path = path.as_ref();
+        let contents =
+            fs::read_to_string(path).map_err(|e| WordSetError::Open(path.into(), e.to_string()))?;
+        Self::parse(path, &contents)
+    }
+
+    /// Loads a built-in word set by name.
+    pub fn builtin(name: &str) -> Result<Self, WordSetError> {
+        match BUILTIN_SETS.iter().find(|(n, _)| *n == name) {
+            Some((name, contents)) => Self::parse(&Path::new("built-in").join(name), contents),
+            None => Err(WordSetError::NotAvailable(name.into())),
+        }
     }
 
-    pub fn choose_with(&self, amount: usize, punct: bool, numbers: bool) -> Vec<String> {
-        let mut chosen = self.choose(amount);
+    /// Parses the contents of a word set file, with `path` used to detect the format and in
+    /// error messages.
+    fn parse(path: &Path, contents: &str) -> Result<Self, WordSetError> {
+        let (meta, entries) = match Format::detect(path, contents) {
+            Format::Monkeytype => {
+                parse_monkeytype(contents).map_err(|e| WordSetError::Json(path.into(), e))?
+            }
+            Format::Csv => (
+                Metadata::default(),
+                parse_list(path, contents, 0, Format::Csv)?,
+            ),
+            Format::Plain => {
+                let (meta, body, offset) = split_header(path, contents)?;
+                (meta, parse_list(path, body, offset, Format::Plain)?)
+            }
+        };
+
+        let issues = lint::check(&entries);
+        let mut skipped = vec![false; entries.len()];
+        for issue in issues.iter().filter(|i| i.skips_entry()) {
+            skipped[issue.entry] = true;
+        }
+
+        let weighted = entries.iter().any(|e| e.weight.is_some());
+        let mut words = Vec::new();
+        let mut cumulative = weighted.then(Vec::new);
+        let mut total = 0.0;
+        for (entry, _) in entries.into_iter().zip(skipped).filter(|(_, s)| !s) {
+            if let Some(cumulative) = &mut cumulative {
+                total += entry.weight.unwrap_or(1.0);
+                cumulative.push(total);
+            }
+            words.push(entry.word);
+        }
+
+        Ok(Self {
+            meta,
+            words,
+            cumulative,
+            model: None,
+            issues,
+        })
+    }
+
+    /// Trains a generated set on a text corpus.
+    ///
+    /// The corpus supplies its own punctuation and capitalization, so punctuation is on by
+    /// default and --no-punct strips it from the generated words instead.
+    pub fn from_corpus<P: AsRef<Path>>(
+        path: P,
+        level: markov::Level,
+        order: usize,
+    ) -> Result<Self, WordSetError> {
+        let path = path.as_ref();
+        let text =
+            fs::read_to_string(path).map_err(|e| WordSetError::Open(path.into(), e.to_string()))?;
+
+        let model = Model::train(&text, level, order);
+        if model.is_empty() {
+            return Err(WordSetError::ShortCorpus(path.into(), order));
+        }
+
+        Ok(Self {
+            meta: Metadata {
+                punct: Some(true),
+                ..Metadata::default()
+            },
+            words: Vec::new(),
+            cumulative: None,
+            model: Some(model),
+            issues: Vec::new(),
+        })
+    }
+
+    /// Resolves a set name against the available sets and loads it.
+    ///
+    /// A name of the form `NAME:N` selects the top `N` words of set `NAME`: those with the largest
+    /// weights if the set is weighted, otherwise the first `N` lines of the file.
+    pub fn resolve(sets: &HashMap<String, SetSource>, name: &str) -> Result<Self, WordSetError> {
+        Self::resolve_depth(sets, name, 0)
+    }
+
+    fn resolve_depth(
+        sets: &HashMap<String, SetSource>,
+        name: &str,
+        depth: usize,
+    ) -> Result<Self, WordSetError> {
+        // Guards against composite sets that include themselves.
+        const MAX_DEPTH: usize = 16;
+        if depth > MAX_DEPTH {
+            return Err(WordSetError::Recursive(name.into()));
+        }
+
+        let (base, top) = match name.rsplit_once(':') {
+            Some((base, n)) if !sets.contains_key(name) => {
+                let n = n
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| WordSetError::InvalidTop(name.into()))?;
+                (base, Some(n))
+            }
+            _ => (name, None),
+        };
+
+        let set = match sets.get(base) {
+            Some(SetSource::File(path)) => Self::load(path)?,
+            Some(SetSource::Builtin(name)) => Self::builtin(name)?,
+            Some(SetSource::Composite(parts)) => {
+                let parts = parts
+                    .iter()
+                    .map(|(name, weight)| {
+                        let set = Self::resolve_depth(sets, name, depth + 1)?;
+                        if set.is_generated() {
+                            return Err(WordSetError::Generated(name.clone()));
+                        }
+                        Ok((set, *weight))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Self::composite(&parts)
+            }
+            Some(SetSource::Markov {
+                corpus,
+                level,
+                order,
+            }) => Self::from_corpus(corpus, *level, *order)?,
+            None => return Err(WordSetError::NotAvailable(base.into())),
+        };
+
+        Ok(match top {
+            Some(_) if set.is_generated() => return Err(WordSetError::Generated(name.into())),
+            Some(n) => set.top(n),
+            None => set,
+        })
+    }
+
+    /// Mixes several sets so that each contributes its share of the total weight.
+    fn composite(parts: &[(WordSet, f64)]) -> Self {
+        let share_total: f64 = parts.iter().map(|(_, w)| w).sum();
+
+        let mut words = Vec::new();
+        let mut cumulative = Vec::new();
+        let mut total = 0.0;
+        for (set, share) in parts {
+            let weights = set.weights();
+            let set_total: f64 = weights.iter().sum();
+            if set_total <= 0.0 || share_total <= 0.0 {
+                continue;
+            }
+            for (word, weight) in set.words.iter().zip(weights) {
+                total += weight / set_total * share / share_total;
+                words.push(word.clone());
+                cumulative.push(total);
+            }
+        }
+
+        Self {
+            meta: Metadata::default(),
+            words,
+            cumulative: Some(cumulative),
+            model: None,
+            issues: Vec::new(),
+        }
+    }
+
+    /// Returns the `n` most frequent words.
+    fn top(&self, n: usize) -> Self {
+        let mut indices: Vec<_> = (0..self.words.len()).collect();
+        let weights = self.weights();
+        if self.cumulative.is_some() {
+            // Stable, so equally weighted words keep their file order.
+            indices.sort_by(|&a, &b| weights[b].total_cmp(&weights[a]));
+        }
+        indices.truncate(n);
+        indices.sort_unstable();
+
+        let mut cumulative = self.cumulative.as_ref().map(|_| Vec::new());
+        let mut total = 0.0;
+        if let Some(cumulative) = &mut cumulative {
+            for &i in &indices {
+                total += weights[i];
+                cumulative.push(total);
+            }
+        }
+
+        Self {
+            meta: self.meta.clone(),
+            words: indices.iter().map(|&i| self.words[i].clone()).collect(),
+            cumulative,
+            model: None,
+            issues: self.issues.clone(),
+        }
+    }
+
+    /// Weight of each word, `1` for unweighted sets.
+    fn weights(&self) -> Vec<f64> {
+        match &self.cumulative {
+            Some(cumulative) => cumulative
+                .iter()
+                .scan(0.0, |prev, &c| {
+                    let weight = c - *prev;
+                    *prev = c;
+                    Some(weight)
+                })
+                .collect(),
+            None => vec![1.0; self.words.len()],
+        }
+    }
+
+    /// Loads only the metadata header of a word set.
+    pub fn load_metadata<P: AsRef<Path>>(path: P) -> Result<Metadata, WordSetError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| WordSetError::Open(path.into(), e.to_string()))?;
+        let mut reader = io::BufReader::new(file);
+
+        let peek = reader
+            .fill_buf()
+            .map_err(|e| WordSetError::Open(path.into(), e.to_string()))?;
+        match Format::detect(path, &String::from_utf8_lossy(peek)) {
+            Format::Monkeytype => {
+                let mut contents = String::new();
+                reader
+                    .read_to_string(&mut contents)
+                    .map_err(|e| WordSetError::Open(path.into(), e.to_string()))?;
+                return parse_monkeytype(&contents)
+                    .map(|(meta, _)| meta)
+                    .map_err(|e| WordSetError::Json(path.into(), e));
+            }
+            Format::Csv => return Ok(Metadata::default()),
+            Format::Plain => (),
+        }
 
-        if numbers {
-            let indices = rand::choose_multiple(0..chosen.len(), amount / 16);
-            for i in indices {
-                chosen[i] = rand::u8(..).to_string();
+        // Only read as far as the end of the header.
+        let mut header = String::new();
+        let mut lines = reader.lines().map_while(Result::ok);
+        if let Some(first) = lines.next() {
+            if first.trim_end() == HEADER_DELIM {
+                header.push_str(&first);
+                header.push('\n');
+                for line in lines {
+                    header.push_str(&line);
+                    header.push('\n');
+                    if line.trim_end() == HEADER_DELIM {
+                        break;
+                    }
+                }
             }
         }
 
-        if punct {
-            punctuate(&mut chosen);
+        split_header(path, &header).map(|(meta, _, _)| meta)
+    }
+
+    /// Chooses `amount` words and applies the modifiers in `options`. Modifiers that join words
+    /// together take more words from the set to keep the count.
+    pub fn choose_with(&self, amount: usize, options: &Options) -> Vec<String> {
+        let filtered;
+        let set = if options.filter.is_empty() {
+            self
+        } else {
+            filtered = self.filter(&options.filter);
+            &filtered
+        };
+        let choose = |amount| match &set.model {
+            Some(model) if options.punct.is_some() => model.generate(amount),
+            Some(model) => generate_plain(model, amount),
+            None => set.choose(amount),
+        };
+
+        let mut chosen = choose(amount);
+        let mut more = iter::repeat_with(|| choose(amount))
+            .take_while(|words| !words.is_empty())
+            .flatten();
+
+        if let Some(numbers) = &options.numbers {
+            numbers.apply(&mut chosen);
+        }
+
+        if let Some(caps) = &options.caps {
+            chosen = caps.apply(chosen.into_iter().chain(&mut more), amount);
+        }
+
+        if let Some(symbols) = &options.symbols {
+            chosen = symbols.apply(chosen.into_iter().chain(&mut more), amount);
+        }
+
+        if let Some(rules) = &options.punct {
+            if !self.is_generated() {
+                chosen = rules.apply(chosen.into_iter().chain(&mut more), amount);
+            }
         }
 
         chosen
     }
 
+    /// Returns the subset of words accepted by the filter, keeping their weights.
+    ///
+    /// Generated sets are returned unfiltered.
+    pub fn filter(&self, filter: &Filter) -> Self {
+        let weights = self.weights();
+        let mut words = Vec::new();
+        let mut cumulative = self.cumulative.as_ref().map(|_| Vec::new());
+        let mut total = 0.0;
+        for (word, weight) in self.words.iter().zip(weights) {
+            if !filter.matches(word) {
+                continue;
+            }
+            words.push(word.clone());
+            if let Some(cumulative) = &mut cumulative {
+                total += weight;
+                cumulative.push(total);
+            }
+        }
+
+        Self {
+            meta: self.meta.clone(),
+            words,
+            cumulative,
+            model: self.model.clone(),
+            issues: self.issues.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.model.is_none()
+    }
+
+    /// Whether the words are generated by a model rather than taken from a list.
+    pub fn is_generated(&self) -> bool {
+        self.model.is_some()
+    }
+
     pub fn choose(&self, amount: usize) -> Vec<String> {
-        rand::choose_multiple(self.words.iter().cloned(), amount)
+        if let Some(model) = &self.model {
+            return model.generate(amount);
+        }
+
+        if self.meta.ordered {
+            // Starts over from the first word if the set runs out.
+            return self.words.iter().cycle().take(amount).cloned().collect();
+        }
+
+        match &self.cumulative {
+            // Sampled with replacement, proportionally to the weights.
+            Some(cumulative) if cumulative.last().is_some_and(|&total| total > 0.0) => (0..amount)
+                .filter_map(|_| rand::choose_cumulative(cumulative))
+                .map(|i| self.words[i].clone())
+                .collect(),
+            _ => choose_uniform(&self.words, amount),
+        }
+    }
+}
+
+/// Settings for generating and typing the words of a test.
+#[derive(Clone, Default, Debug)]
+pub struct Options {
+    /// Punctuation rules, if punctuation is enabled.
+    pub punct: Option<punct::Rules>,
+    /// Number settings, if numbers are enabled.
+    pub numbers: Option<Numbers>,
+    /// Capitalization settings, if random capitalization is enabled.
+    pub caps: Option<Caps>,
+    /// Symbol settings, if symbols are enabled.
+    pub symbols: Option<Symbols>,
+    pub filter: Filter,
+    /// Lazy matching settings, if lazy mode is enabled.
+    pub lazy: Option<Lazy>,
+    /// Compose sequences, if composing is enabled.
+    pub compose: Option<Compose>,
+    /// Layout emulated over the QWERTY system layout, if any.
+    pub layout: Option<Layout>,
+}
+
+/// Restricts which words of a set may be chosen.
+///
+/// Lengths are counted in characters, and letters are compared case-insensitively.
+#[derive(Clone, Default, Debug)]
+pub struct Filter {
+    pub min_len: Option<usize>,
+    pub max_len: Option<usize>,
+    /// Only use words made up entirely of these letters.
+    pub only: Option<String>,
+    /// Only use words containing at least one of these letters.
+    pub contains: Option<String>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self.min_len.is_none()
+            && self.max_len.is_none()
+            && self.only.is_none()
+            && self.contains.is_none()
+    }
+
+    pub fn matches(&self, word: &str) -> bool {
+        let len = word.chars().count();
+        if self.min_len.is_some_and(|min| len < min) || self.max_len.is_some_and(|max| len > max) {
+            return false;
+        }
+
+        let word = word.to_lowercase();
+        if let Some(only) = &self.only {
+            let only = only.to_lowercase();
+            if !word.chars().all(|c| only.contains(c)) {
+                return false;
+            }
+        }
+        if let Some(contains) = &self.contains {
+            let contains = contains.to_lowercase();
+            if !word.chars().any(|c| contains.contains(c)) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Describes the filter as it is recorded with results, e.g. `max length 5, only 'asdf'`.
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(min) = self.min_len {
+            parts.push(format!("min length {}", min));
+        }
+        if let Some(max) = self.max_len {
+            parts.push(format!("max length {}", max));
+        }
+        if let Some(only) = &self.only {
+            parts.push(format!("only '{}'", only.to_lowercase()));
+        }
+        if let Some(contains) = &self.contains {
+            parts.push(format!("containing '{}'", contains.to_lowercase()));
+        }
+        write!(f, "{}", parts.join(", "))
     }
 }
 
+/// Generates words with the corpus punctuation and capitalization stripped.
+fn generate_plain(model: &Model, amount: usize) -> Vec<String> {
+    // Bounds the retries for a corpus made up mostly of punctuation.
+    const MAX_ROUNDS: usize = 8;
+
+    let mut words = Vec::with_capacity(amount);
+    for _ in 0..MAX_ROUNDS {
+        if words.len() >= amount {
+            break;
+        }
+        words.extend(
+            model
+                .generate(amount - words.len())
+                .into_iter()
+                .map(|word| {
+                    word.trim_matches(|c: char| !c.is_alphanumeric())
+                        .to_lowercase()
+                })
+                .filter(|word| !word.is_empty()),
+        );
+    }
+    words
+}
+
+/// Samples `amount` words in random order, using every word once before any is repeated.
+fn choose_uniform(words: &[String], amount: usize) -> Vec<String> {
+    let mut chosen = Vec::with_capacity(amount);
+    while chosen.len() < amount && !words.is_empty() {
+        let mut round = rand::choose_multiple(words.iter().cloned(), amount - chosen.len());
+        // The reservoir keeps the words in file order.
+        rand::shuffle(&mut round);
+        chosen.append(&mut round);
+    }
+    chosen
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Format {
+    Plain,
+    Csv,
+    Monkeytype,
+}
+
+impl Format {
+    fn detect(path: &Path, contents: &str) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Self::Monkeytype,
+            Some("csv") => Self::Csv,
+            Some("txt" | "tsv") => Self::Plain,
+            _ if contents.trim_start().starts_with('{') => Self::Monkeytype,
+            _ => Self::Plain,
+        }
+    }
+}
+
+/// Parses word lines into entries, with a weight for lines that have a second column.
+///
+/// Blank lines are kept as empty entries for [`lint::check`] to report. `offset` is the number of
+/// lines preceding `body` in the file, for locations and error messages.
+fn parse_list(
+    path: &Path,
+    body: &str,
+    offset: usize,
+    format: Format,
+) -> Result<Vec<Entry>, WordSetError> {
+    let mut entries = Vec::new();
+    for (i, line) in body.lines().enumerate() {
+        let line = line.trim();
+        let location = Location::Line(offset + i + 1);
+
+        let (word, weight) = match format {
+            // Columns after the weight, such as a rank, are ignored.
+            Format::Csv => {
+                let mut fields = csv_fields(line).into_iter();
+                (fields.next().unwrap_or_default(), fields.next())
+            }
+            _ => match line.split_once(char::is_whitespace) {
+                Some((word, weight)) => (word.into(), Some(weight.into())),
+                None => (line.into(), None),
+            },
+        };
+        let (word, weight) = match weight {
+            Some(weight) => match weight.trim().parse::<f64>() {
+                Ok(w) if w.is_finite() && w >= 0.0 => (word.trim().into(), Some(w)),
+                // CSV files often start with a header naming the columns.
+                Err(_) if format == Format::Csv && i == 0 => continue,
+                // Not a weight column, so the entry itself contains whitespace.
+                Err(_) if format == Format::Plain && !looks_numeric(&weight) => (line.into(), None),
+                _ => {
+                    return Err(WordSetError::InvalidWeight(
+                        path.into(),
+                        offset + i + 1,
+                        weight,
+                    ))
+                }
+            },
+            None => (word.trim().into(), None),
+        };
+        entries.push(Entry {
+            location,
+            word,
+            weight,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Splits a CSV line into fields. Quoted fields may contain commas, and `""` in them stands
+/// for a quote.
+fn csv_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+/// Whether a column looks like it was meant to be a number.
+fn looks_numeric(column: &str) -> bool {
+    column
+        .trim()
+        .starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'))
+}
+
+/// Parses a monkeytype language file.
+fn parse_monkeytype(contents: &str) -> Result<(Metadata, Vec<Entry>), String> {
+    let value: serde_json::Value = serde_json::from_str(contents).map_err(|e| e.to_string())?;
+
+    let entries = value
+        .get("words")
+        .and_then(|w| w.as_array())
+        .ok_or("missing 'words' array")?
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            w.as_str().map(|w| Entry {
+                location: Location::Index(i + 1),
+                word: w.trim().into(),
+                weight: None,
+            })
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or("'words' must only contain strings")?;
+
+    let meta = Metadata {
+        name: value.get("name").and_then(|v| v.as_str()).map(Into::into),
+        language: value.get("bcp47").and_then(|v| v.as_str()).map(Into::into),
+        direction: match value.get("rightToLeft").and_then(|v| v.as_bool()) {
+            Some(true) => Direction::Rtl,
+            _ => Direction::Ltr,
+        },
+        ..Metadata::default()
+    };
+
+    Ok((meta, entries))
+}
+
+const HEADER_DELIM: &str = "+++";
+
+/// Optional information about a word set, given as a TOML header delimited by `+++` lines at the
+/// start of the file:
+///
+/// ```text
+/// +++
+/// name = "English 1k"
+/// language = "en"
+/// direction = "ltr"
+/// ordered = false
+/// punct = false
+/// description = "The 1000 most common English words."
+///
+/// [punctuation]
+/// terminal = 0.2
+/// +++
+/// the
+/// of
+/// ...
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct Metadata {
+    /// Display name.
+    pub name: Option<String>,
+    /// Language tag, e.g. `en` or `pt-BR`.
+    pub language: Option<String>,
+    /// Writing direction.
+    pub direction: Direction,
+    /// Whether words are used in file order rather than sampled.
+    pub ordered: bool,
+    /// Whether punctuation is enabled by default, unless overridden by --punct/--no-punct.
+    pub punct: Option<bool>,
+    /// Punctuation rules, applied over the built-in rules for the language and the config.
+    pub punctuation: Option<punct::Overrides>,
+    pub description: Option<String>,
+}
+
+impl Metadata {
+    fn from_value(value: &toml::Value) -> Result<Self, String> {
+        fn get_str(value: &toml::Value, key: &str) -> Result<Option<String>, String> {
+            match value.get(key) {
+                Some(v) => v
+                    .as_str()
+                    .map(|s| Some(s.into()))
+                    .ok_or_else(|| format!("'{}' must be a string", key)),
+                None => Ok(None),
+            }
+        }
+
+        fn get_bool(value: &toml::Value, key: &str) -> Result<Option<bool>, String> {
+            match value.get(key) {
+                Some(v) => v
+                    .as_bool()
+                    .map(Some)
+                    .ok_or_else(|| format!("'{}' must be true or false", key)),
+                None => Ok(None),
+            }
+        }
+
+        let direction = match get_str(value, "direction")? {
+            Some(dir) => dir.parse()?,
+            None => Direction::default(),
+        };
+
+        Ok(Self {
+            name: get_str(value, "name")?,
+            language: get_str(value, "language")?,
+            direction,
+            ordered: get_bool(value, "ordered")?.unwrap_or(false),
+            punct: get_bool(value, "punct")?,
+            punctuation: value
+                .get("punctuation")
+                .map(punct::Overrides::from_value)
+                .transpose()?,
+            description: get_str(value, "description")?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Direction {
+    #[default]
+    Ltr,
+    Rtl,
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "ltr" => Ok(Self::Ltr),
+            "rtl" => Ok(Self::Rtl),
+            _ => Err(format!("Invalid direction '{}': must be 'ltr' or 'rtl'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ltr => write!(f, "ltr"),
+            Self::Rtl => write!(f, "rtl"),
+        }
+    }
+}
+
+/// Splits the metadata header from the word list, returning the parsed header, the remaining
+/// contents and the number of lines consumed by the header.
+fn split_header<'a>(
+    path: &Path,
+    contents: &'a str,
+) -> Result<(Metadata, &'a str, usize), WordSetError> {
+    let rest = match contents.strip_prefix(HEADER_DELIM) {
+        Some(rest) if rest.starts_with('\n') || rest.starts_with("\r\n") => rest,
+        _ => return Ok((Metadata::default(), contents, 0)),
+    };
+
+    let mut offset = 0;
+    for (lines, line) in rest.split_inclusive('\n').enumerate() {
+        if offset > 0 && line.trim_end() == HEADER_DELIM {
+            let header = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            let meta = header
+                .parse::<toml::Value>()
+                .map_err(|e| e.to_string())
+                .and_then(|value| Metadata::from_value(&value))
+                .map_err(|e| WordSetError::Header(path.into(), e))?;
+            return Ok((meta, body, lines + 1));
+        }
+        offset += line.len();
+    }
+
+    Err(WordSetError::Header(
+        path.into(),
+        format!("Unterminated header: missing closing '{}'", HEADER_DELIM),
+    ))
+}
+
 #[derive(Debug)]
 pub enum WordSetError {
     Open(PathBuf, String),
+    InvalidWeight(PathBuf, usize, String),
+    Header(PathBuf, String),
+    Json(PathBuf, String),
+    NotAvailable(String),
+    InvalidTop(String),
+    Recursive(String),
+    Generated(String),
+    ShortCorpus(PathBuf, usize),
 }
 
 impl fmt::Display for WordSetError {
@@ -59,46 +849,188 @@ impl fmt::Display for WordSetError {
                 path.display(),
                 e
             ),
+            Self::InvalidWeight(path, line, weight) => write!(
+                f,
+                "Invalid weight '{}' on line {} of word set file '{}'",
+                weight,
+                line,
+                path.display()
+            ),
+            Self::Header(path, e) => write!(
+                f,
+                "Invalid header in word set file '{}': {}",
+                path.display(),
+                e
+            ),
+            Self::Json(path, e) => write!(
+                f,
+                "Invalid monkeytype language file '{}': {}",
+                path.display(),
+                e
+            ),
+            Self::NotAvailable(name) => write!(f, "Word set '{}' is not available", name),
+            Self::InvalidTop(name) => {
+                write!(f, "Invalid word set '{}': expected NAME:N with N > 0", name)
+            }
+            Self::Generated(name) => write!(
+                f,
+                "Generated word set '{}' cannot be used with NAME:N or in a composite set",
+                name
+            ),
+            Self::ShortCorpus(path, order) => write!(
+                f,
+                "Corpus '{}' is too short for a model of order {}",
+                path.display(),
+                order
+            ),
+            Self::Recursive(name) => write!(
+                f,
+                "Word set '{}' is nested too deeply (does a composite set include itself?)",
+                name
+            ),
         }
     }
 }
 
-fn punctuate(words: &mut [String]) {
-    const TERMINAL: &[char] = &['.', '?', '!'];
-    const PAUSE: &[char] = &[',', ';', ':'];
-    const SEP: &[&str] = &["-", "/", "..."];
-    const DELIM: &[(char, char)] = &[
-        ('"', '"'),
-        ('\'', '\''),
-        ('(', ')'),
-        ('[', ']'),
-        ('{', '}'),
-        ('<', '>'),
-    ];
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut last_terminal = false;
-    for word in words {
-        if last_terminal {
-            if let Some(f) = word.get_mut(..1) {
-                f.make_ascii_uppercase();
-            }
-            last_terminal = false;
+    fn set(words: &[&str]) -> WordSet {
+        WordSet {
+            meta: Metadata::default(),
+            words: words.iter().map(|&w| w.into()).collect(),
+            cumulative: None,
+            model: None,
+            issues: Vec::new(),
         }
+    }
 
-        // A bit ad-hoc, could improve this.
-        let r = rand::f64();
-        if r < 0.3 {
-            if r > 0.2 {
-                word.push(TERMINAL[rand::usize(..TERMINAL.len())]);
-                last_terminal = true;
-            } else if r > 0.1 {
-                word.push(PAUSE[rand::usize(..PAUSE.len())]);
-            } else if r > 0.05 {
-                let (l, r) = DELIM[rand::usize(..DELIM.len())];
-                *word = format!("{}{}{}", l, word, r);
-            } else {
-                *word = SEP[rand::usize(..SEP.len())].to_string();
-            }
+    #[test]
+    fn small_sets_repeat_words() {
+        let chosen = set(&["a", "b", "c"]).choose(10);
+        assert_eq!(chosen.len(), 10);
+        for word in ["a", "b", "c"] {
+            assert!(chosen[..3].contains(&word.to_string()));
+        }
+    }
+
+    #[test]
+    fn top_and_composite_sets_fill_the_count() {
+        let mut weighted = set(&["a", "b", "c", "d"]);
+        weighted.cumulative = Some(vec![4.0, 7.0, 9.0, 10.0]);
+        assert_eq!(weighted.top(2).words, ["a", "b"]);
+        assert_eq!(weighted.top(2).choose(50).len(), 50);
+        assert_eq!(set(&["a", "b", "c", "d"]).top(2).choose(50).len(), 50);
+
+        let mut ordered = set(&["a", "b", "c"]).top(2);
+        ordered.meta.ordered = true;
+        assert_eq!(ordered.choose(5), ["a", "b", "a", "b", "a"]);
+
+        let composite = WordSet::composite(&[(weighted.top(2), 1.0), (set(&["x"]), 1.0)]);
+        assert_eq!(composite.choose(50).len(), 50);
+    }
+
+    #[test]
+    fn punctuation_keeps_the_count() {
+        let mut rules = punct::Rules {
+            terminal: 0.2,
+            delim: 0.2,
+            sep: 0.3,
+            join: 0.3,
+            ..Default::default()
+        };
+        rules.openers.insert("?".into(), "¿".into());
+        rules.spaced = vec!["?".into(), "¿".into(), "(".into(), ")".into()];
+        let options = Options {
+            punct: Some(rules),
+            ..Default::default()
+        };
+        for count in [1, 2, 3, 50] {
+            assert_eq!(
+                set(&["a", "b", "c"]).choose_with(count, &options).len(),
+                count
+            );
         }
     }
+
+    #[test]
+    fn compound_caps_keep_the_count() {
+        let options = Options {
+            caps: Some(Caps {
+                fraction: 1.0,
+                styles: vec![(crate::caps::Style::Snake, 1.0)],
+            }),
+            ..Default::default()
+        };
+        let chosen = set(&["a", "b", "c"]).choose_with(50, &options);
+        assert_eq!(chosen.len(), 50);
+        assert!(chosen.iter().all(|word| word.contains('_')));
+    }
+
+    #[test]
+    fn symbols_keep_the_count() {
+        let options = Options {
+            symbols: Some(Symbols {
+                density: 1.0,
+                patterns: vec![("% && %".into(), 1.0), ("--".into(), 1.0)],
+            }),
+            ..Default::default()
+        };
+        for count in [1, 2, 50] {
+            let chosen = set(&["a", "b", "c"]).choose_with(count, &options);
+            assert_eq!(chosen.len(), count);
+        }
+    }
+
+    fn parse(body: &str, format: Format) -> Vec<(String, Option<f64>)> {
+        parse_list(Path::new("test"), body, 0, format)
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.word, entry.weight))
+            .collect()
+    }
+
+    #[test]
+    fn parses_csv() {
+        let entries = parse(
+            "word,count,rank\n\"the\",5000,1\n\"a, b\",20,2\n\"say \"\"hi\"\"\",3\nlone\n",
+            Format::Csv,
+        );
+        assert_eq!(
+            entries,
+            [
+                ("the".into(), Some(5000.0)),
+                ("a, b".into(), Some(20.0)),
+                ("say \"hi\"".into(), Some(3.0)),
+                ("lone".into(), None),
+            ]
+        );
+        assert!(parse_list(Path::new("test"), "the,5\na,lots\n", 0, Format::Csv).is_err());
+    }
+
+    #[test]
+    fn parses_plain_weights() {
+        let entries = parse("the\t5000\nice cream\nof 12.5\n", Format::Plain);
+        assert_eq!(
+            entries,
+            [
+                ("the".into(), Some(5000.0)),
+                ("ice cream".into(), None),
+                ("of".into(), Some(12.5)),
+            ]
+        );
+        assert!(parse_list(Path::new("test"), "the -5\n", 0, Format::Plain).is_err());
+    }
+
+    #[test]
+    fn detects_formats() {
+        let detect = |path: &str, contents: &str| Format::detect(Path::new(path), contents);
+        assert_eq!(detect("a.json", "the"), Format::Monkeytype);
+        assert_eq!(detect("a.csv", "{"), Format::Csv);
+        assert_eq!(detect("a.txt", "{"), Format::Plain);
+        assert_eq!(detect("a.tsv", "the\t1"), Format::Plain);
+        assert_eq!(detect("french", "  {\"words\": []}"), Format::Monkeytype);
+        assert_eq!(detect("french", "the,1"), Format::Plain);
+    }
 }